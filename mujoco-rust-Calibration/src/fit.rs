@@ -0,0 +1,62 @@
+// fit.rs
+
+/// Least squares fit of `y = coef[0] * columns[0] + coef[1] * columns[1] + ...`
+#[derive(Debug, Clone)]
+pub struct Fit {
+    pub coef: Vec<f64>,
+    pub r2: f64, // share of the variance of y explained by the fit
+}
+
+/// Solved through the normal equations; a column that is zero throughout,
+/// e.g. the yaw input on the x rig, gets a coefficient of 0 instead of
+/// making them singular
+pub fn least_squares(columns: &[Vec<f64>], y: &[f64]) -> Fit {
+    let used: Vec<usize> = (0..columns.len()).filter(|&j| columns[j].iter().any(|v| v.abs() > 1e-9)).collect();
+    let n = used.len();
+
+    // [A^T A | A^T y]
+    let mut m = vec![vec![0.0; n + 1]; n];
+    for (r, &i) in used.iter().enumerate() {
+        for (c, &j) in used.iter().enumerate() {
+            m[r][c] = dot(&columns[i], &columns[j]);
+        }
+        m[r][n] = dot(&columns[i], y);
+    }
+
+    // Gauss-Jordan with partial pivoting
+    for col in 0..n {
+        let pivot = (col..n).max_by(|&a, &b| m[a][col].abs().total_cmp(&m[b][col].abs())).unwrap();
+        m.swap(col, pivot);
+        if m[col][col].abs() < 1e-12 {
+            continue;
+        }
+        let row = m[col].clone();
+        for (r, other) in m.iter_mut().enumerate() {
+            if r != col {
+                let factor = other[col] / row[col];
+                for k in col..=n {
+                    other[k] -= factor * row[k];
+                }
+            }
+        }
+    }
+    let mut coef = vec![0.0; columns.len()];
+    for (r, &j) in used.iter().enumerate() {
+        if m[r][r].abs() >= 1e-12 {
+            coef[j] = m[r][n] / m[r][r];
+        }
+    }
+
+    let mean = y.iter().sum::<f64>() / y.len().max(1) as f64;
+    let (mut residual, mut total) = (0.0, 0.0);
+    for (k, &v) in y.iter().enumerate() {
+        let fitted: f64 = columns.iter().zip(coef.iter()).map(|(c, b)| c[k] * b).sum();
+        residual += (v - fitted).powi(2);
+        total += (v - mean).powi(2);
+    }
+    Fit { coef, r2: if total > 0.0 { 1.0 - residual / total } else { 1.0 } }
+}
+
+fn dot(a: &[f64], b: &[f64]) -> f64 {
+    a.iter().zip(b.iter()).map(|(x, y)| x * y).sum()
+}
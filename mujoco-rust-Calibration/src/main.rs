@@ -0,0 +1,146 @@
+// main.rs
+mod fit;
+mod rig;
+
+use mujoco_compose::compose;
+use rig::{Rig, Sample, Test};
+use std::error::Error;
+use std::path::{Path, PathBuf};
+
+// usage: mujoco-rust-Calibration [--dir <folder of x2.xml>] [--out <dir>] [--mass <kg>] [--distance <m>] [--amplitude <ctrl>]
+
+/// Per unit of inertia (or mass) coefficients of one run
+struct Response {
+    thrust: f64, // k_T / I
+    yaw: f64,    // k_Q / I
+    r2: f64,
+}
+
+/// One line of the report
+struct Row {
+    parameter: String,
+    identified: f64,
+    model: f64,
+}
+
+fn main() -> Result<(), Box<dyn Error>> {
+    let mut dir = PathBuf::from("../x2");
+    let mut out = PathBuf::from(".");
+    let mut mass = 0.1; // kg, of each of the two added masses
+    let mut distance = 0.25; // m, of the added masses from the axis
+    let mut test = Test::default();
+
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--dir" => dir = args.next().map(PathBuf::from).unwrap_or(dir),
+            "--out" => out = args.next().map(PathBuf::from).unwrap_or(out),
+            "--mass" => mass = args.next().and_then(|s| s.parse().ok()).unwrap_or(mass),
+            "--distance" => distance = args.next().and_then(|s| s.parse().ok()).unwrap_or(distance),
+            "--amplitude" => test.amplitude = args.next().and_then(|s| s.parse().ok()).unwrap_or(test.amplitude),
+            _ => eprintln!("unknown argument: {}", arg),
+        }
+    }
+    std::fs::create_dir_all(&out)?;
+
+    println!("Calibration Start...");
+    println!("--------------------------------------------------");
+
+    let mut rows = Vec::new();
+    let mut fits = Vec::new();
+
+    // thrust stand: the x2 on a vertical slide, its weight is known
+    let mut composer = compose::Composer::new(dir.join("scene.xml"));
+    let mut drone = compose::Drone::new(dir.join("x2.xml"));
+    drone.pos = Some([0.0, 0.0, 1.0]);
+    drone.mount = compose::Mount::slide(2);
+    composer.drones.push(drone);
+    let stand = Rig::load("thrust_stand", &composer.write_temp()?)?;
+    let response = identify(&stand, &run(&stand, &test, &out)?);
+    let weight = stand.reference.inertia;
+    fits.push((stand.name.clone(), response.r2));
+    rows.push(Row { parameter: "thrust coefficient, stand (N)".into(), identified: response.thrust * weight, model: stand.reference.thrust });
+
+    // the hinge rigs as they are and with two masses of known inertia:
+    // k / I and k / (I + added) give both k and I
+    for (axis, file) in [("x", "x_axis_scene.xml"), ("y", "y_axis_scene.xml"), ("z", "z_axis_scene.xml")] {
+        let bare = Rig::load(&format!("{}_axis", axis), &dir.join(file))?;
+        let mut root = compose::Composer::new(dir.join(file)).compose()?;
+        let added = rig::add_masses(&mut root, mass, distance)?;
+        let path = std::env::temp_dir().join(format!("{}_axis_loaded_{}.xml", axis, std::process::id()));
+        std::fs::write(&path, root.to_string())?;
+        let loaded = Rig::load(&format!("{}_axis_loaded", axis), &path)?;
+
+        let a = identify(&bare, &run(&bare, &test, &out)?);
+        let b = identify(&loaded, &run(&loaded, &test, &out)?);
+        fits.push((bare.name.clone(), a.r2));
+        fits.push((loaded.name.clone(), b.r2));
+
+        // the motors turn a hinge along x or y by their thrust, along z by their yaw torque
+        let (yaw, ka, kb) = if axis == "z" { (true, a.yaw, b.yaw) } else { (false, a.thrust, b.thrust) };
+        if (ka - kb).abs() < 1e-12 {
+            eprintln!("{}: the added masses made no difference", bare.name);
+            continue;
+        }
+        let inertia = added * kb / (ka - kb);
+        rows.push(Row { parameter: format!("inertia about the {} axis (kg m²)", axis), identified: inertia, model: bare.reference.inertia });
+        rows.push(match yaw {
+            true => Row { parameter: format!("yaw torque coefficient, {} rig (Nm)", axis), identified: ka * inertia, model: bare.reference.yaw },
+            false => Row { parameter: format!("thrust coefficient, {} rig (N)", axis), identified: ka * inertia, model: bare.reference.thrust },
+        });
+    }
+
+    println!("--------------------------------------------------");
+    let report = report(&dir, weight, &rows, &fits);
+    print!("{}", report);
+    let path = out.join("calibration_report.md");
+    std::fs::write(&path, report)?;
+    println!("--------------------------------------------------");
+    println!("report written to {}", path.display());
+    Ok(())
+}
+
+/// Run a test, log it to `<out>/calibration_<rig>.csv`
+fn run(rig: &Rig, test: &Test, out: &Path) -> Result<Vec<Sample>, Box<dyn Error>> {
+    let samples = rig.run(test);
+    let path = out.join(format!("calibration_{}.csv", rig.name));
+    rig::write_csv(&path, &samples)?;
+    let range = samples.iter().fold([f64::INFINITY, f64::NEG_INFINITY], |[lo, hi], s| [lo.min(s.qpos), hi.max(s.qpos)]);
+    println!("{}: {} samples, joint within [{:.3}, {:.3}] -> {}", rig.name, samples.len(), range[0], range[1], path.display());
+    Ok(samples)
+}
+
+/// Fit `qacc = a_T * thrust moment + a_Q * yaw moment + gravity + damping`
+///
+/// The moments are the ctrl times the arms of the rig, gravity is a
+/// constant on the slide and `sin`/`cos` of the angle on a hinge, the
+/// centre of mass is not on the axis.
+fn identify(rig: &Rig, samples: &[Sample]) -> Response {
+    let moment = |arms: &[f64]| -> Vec<f64> { samples.iter().map(|s| s.ctrl.iter().zip(arms.iter()).map(|(c, a)| c * a).sum()).collect() };
+    let mut columns = vec![moment(&rig.thrust_arm), moment(&rig.yaw_arm)];
+    if rig.slide {
+        columns.push(vec![1.0; samples.len()]);
+    } else {
+        columns.push(samples.iter().map(|s| s.qpos.sin()).collect());
+        columns.push(samples.iter().map(|s| s.qpos.cos()).collect());
+    }
+    columns.push(samples.iter().map(|s| s.qvel).collect());
+    let qacc: Vec<f64> = samples.iter().map(|s| s.qacc).collect();
+    let fit = fit::least_squares(&columns, &qacc);
+    Response { thrust: fit.coef[0], yaw: fit.coef[1], r2: fit.r2 }
+}
+
+fn report(dir: &Path, weight: f64, rows: &[Row], fits: &[(String, f64)]) -> String {
+    let mut text = format!("# Calibration of {}\n\n", dir.join("x2.xml").display());
+    text += &format!("Mass on the thrust stand {:.4} kg, as weighed.\n\n", weight);
+    text += "| parameter | identified | x2.xml | error |\n|---|---|---|---|\n";
+    for row in rows {
+        let error = (row.identified - row.model) / row.model * 100.0;
+        text += &format!("| {} | {:.5} | {:.5} | {:+.2}% |\n", row.parameter, row.identified, row.model, error);
+    }
+    text += "\n| run | R² |\n|---|---|\n";
+    for (name, r2) in fits {
+        text += &format!("| {} | {:.6} |\n", name, r2);
+    }
+    text
+}
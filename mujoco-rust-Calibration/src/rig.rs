@@ -0,0 +1,250 @@
+// rig.rs
+use mujoco_compose::mjcf::Element;
+use mujoco_rust::Simulation;
+use std::error::Error;
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::Path;
+
+// mjtJoint
+const JNT_SLIDE: i32 = 2;
+// mjtTrn
+const TRN_SITE: i32 = 4;
+// mjDSBL_CONTACT in MuJoCo 2.3.5
+const DSBL_CONTACT: i32 = 1 << 4;
+// mjNGAIN in MuJoCo 2.3.5
+const NGAIN: usize = 10;
+
+/// A model with a single joint, the drone on a slide or a hinge
+///
+/// The thrust and yaw torque of each motor on the joint are split into the
+/// unknown coefficient and the known geometry, the arms: a motor with ctrl
+/// `c` gives `k_T * thrust_arm * c + k_Q * yaw_arm * c` along the joint,
+/// a force on a slide, a torque on a hinge.
+pub struct Rig {
+    pub name: String,
+    pub simulation: Simulation,
+    pub slide: bool,
+    pub motors: Vec<usize>,       // actuators with a site transmission
+    pub thrust_arm: Vec<f64>,     // per motor, m on a hinge, 1 along a slide
+    pub yaw_arm: Vec<f64>,        // per motor, the sense of its yaw torque about the hinge
+    pub ctrlrange: Vec<[f64; 2]>, // per motor
+    pub reference: Reference,
+}
+
+/// What the model says, to compare the fit with
+#[derive(Debug, Clone, Copy)]
+pub struct Reference {
+    pub thrust: f64,  // N per unit ctrl, |gear force| * gain
+    pub yaw: f64,     // Nm per unit ctrl, |gear torque| * gain
+    pub inertia: f64, // qM of the joint: kg m² about the hinge, kg on a slide
+}
+
+impl Rig {
+    pub fn load(name: &str, path: &Path) -> Result<Self, Box<dyn Error>> {
+        let model = mujoco_rust::Model::from_xml(path.to_string_lossy().to_string()).map_err(|e| format!("{}: {:?}", path.display(), e))?;
+        let simulation = Simulation::new(model);
+        let mj_model = unsafe { &*simulation.model.ptr() };
+        if mj_model.nv != 1 {
+            return Err(format!("{}: a rig has one degree of freedom, this model has {}", path.display(), mj_model.nv).into());
+        }
+        // the z rig's rod goes through the drone
+        unsafe { (*simulation.model.ptr()).opt.disableflags |= DSBL_CONTACT };
+
+        // one step for the poses, the arms do not change as the joint moves
+        simulation.step();
+        let mut rig = Rig {
+            name: name.to_string(),
+            slide: unsafe { *mj_model.jnt_type } == JNT_SLIDE,
+            motors: Vec::new(),
+            thrust_arm: Vec::new(),
+            yaw_arm: Vec::new(),
+            ctrlrange: Vec::new(),
+            reference: Reference { thrust: 0.0, yaw: 0.0, inertia: unsafe { *(*simulation.state.ptr()).qM } },
+            simulation,
+        };
+        unsafe {
+            let mj_data = &*rig.simulation.state.ptr();
+            let anchor = read3(mj_data.xanchor);
+            let axis = read3(mj_data.xaxis);
+            for actuator in 0..mj_model.nu as usize {
+                if *mj_model.actuator_trntype.add(actuator) != TRN_SITE {
+                    continue;
+                }
+                let site = *mj_model.actuator_trnid.add(2 * actuator) as usize;
+                let pos = read3(mj_data.site_xpos.add(3 * site));
+                let rot = std::slice::from_raw_parts(mj_data.site_xmat.add(9 * site), 9);
+                let gear = std::slice::from_raw_parts(mj_model.actuator_gear.add(6 * actuator), 6);
+                let gain = *mj_model.actuator_gainprm.add(NGAIN * actuator);
+                // unit directions of the gear force & torque in the world
+                let force = unit(&mat_vec(rot, &[gear[0], gear[1], gear[2]]));
+                let torque = unit(&mat_vec(rot, &[gear[3], gear[4], gear[5]]));
+                let r = [pos[0] - anchor[0], pos[1] - anchor[1], pos[2] - anchor[2]];
+                rig.thrust_arm.push(if rig.slide { dot(&force, &axis) } else { dot(&cross(&r, &force), &axis) });
+                rig.yaw_arm.push(if rig.slide { 0.0 } else { dot(&torque, &axis) });
+                rig.ctrlrange.push([*mj_model.actuator_ctrlrange.add(2 * actuator), *mj_model.actuator_ctrlrange.add(2 * actuator + 1)]);
+                rig.motors.push(actuator);
+                rig.reference.thrust = norm(&[gear[0], gear[1], gear[2]]) * gain;
+                rig.reference.yaw = norm(&[gear[3], gear[4], gear[5]]) * gain;
+            }
+        }
+        rig.simulation.reset();
+        Ok(rig)
+    }
+
+    /// Run a test from the initial state and log every step
+    pub fn run(&self, test: &Test) -> Vec<Sample> {
+        self.simulation.reset();
+        let mj_model = unsafe { &*self.simulation.model.ptr() };
+        let mj_data = unsafe { &*self.simulation.state.ptr() };
+        let timestep = mj_model.opt.timestep;
+
+        // the motors push the joint one way, scaled to 1 for the largest arm
+        let pattern: Vec<f64> = self.thrust_arm.iter().zip(self.yaw_arm.iter()).map(|(t, y)| t + y).collect();
+        let largest = pattern.iter().fold(0.0_f64, |m, p| m.max(p.abs())).max(1e-12);
+
+        let mut ctrl = vec![0.0; mj_model.nu as usize];
+        let mut samples = Vec::new();
+        let steps = (test.duration / timestep).round() as usize;
+        for _ in 0..steps {
+            let (time, qpos, qvel) = unsafe { (mj_data.time, *mj_data.qpos, *mj_data.qvel) };
+            let input = signal(time, test.amplitude) - test.kp * qpos - test.kd * qvel;
+            for (i, &actuator) in self.motors.iter().enumerate() {
+                let [min, max] = self.ctrlrange[i];
+                ctrl[actuator] = (test.base + input * pattern[i] / largest).clamp(min, max);
+            }
+            self.simulation.control(&ctrl);
+            self.simulation.step();
+            // qacc of the step, at the state before it
+            let qacc = unsafe { *mj_data.qacc };
+            samples.push(Sample { time, ctrl: self.motors.iter().map(|&a| ctrl[a]).collect(), qpos, qvel, qacc });
+        }
+        samples
+    }
+}
+
+/// Inputs of a run: every motor at `base`, plus the signal and a PD loop
+/// that keeps the joint near 0, on the motors that move it
+#[derive(Debug, Clone, Copy)]
+pub struct Test {
+    pub base: f64,      // ctrl of every motor
+    pub amplitude: f64, // of the steps & sines, in ctrl
+    pub kp: f64,        // ctrl per rad (m)
+    pub kd: f64,        // ctrl per rad/s (m/s)
+    pub duration: f64,  // s
+}
+
+impl Default for Test {
+    fn default() -> Self {
+        // 3.25 holds the weight of the x2 on the slide, the hinges are
+        // inverted pendulums that the loop has to hold up
+        Test { base: 3.25, amplitude: 1.0, kp: 8.0, kd: 2.0, duration: 14.0 }
+    }
+}
+
+/// At rest for 1s, a step up & a step down of 2s, then 3s of sine at
+/// 0.5, 1 and 2Hz
+pub fn signal(time: f64, amplitude: f64) -> f64 {
+    let sine = |hz: f64| amplitude * (2.0 * std::f64::consts::PI * hz * time).sin();
+    match time {
+        t if t < 1.0 => 0.0,
+        t if t < 3.0 => amplitude,
+        t if t < 5.0 => -amplitude,
+        t if t < 8.0 => sine(0.5),
+        t if t < 11.0 => sine(1.0),
+        _ => sine(2.0),
+    }
+}
+
+/// One step of a run
+#[derive(Debug, Clone)]
+pub struct Sample {
+    pub time: f64,
+    pub ctrl: Vec<f64>, // of the motors
+    pub qpos: f64,
+    pub qvel: f64,
+    pub qacc: f64,
+}
+
+pub fn write_csv(path: &Path, samples: &[Sample]) -> std::io::Result<()> {
+    let mut out = BufWriter::new(File::create(path)?);
+    let motors = samples.first().map_or(0, |s| s.ctrl.len());
+    let ctrl: Vec<String> = (1..=motors).map(|i| format!("ctrl{}", i)).collect();
+    writeln!(out, "time,{},qpos,qvel,qacc", ctrl.join(","))?;
+    for s in samples {
+        let ctrl: Vec<String> = s.ctrl.iter().map(|c| c.to_string()).collect();
+        writeln!(out, "{},{},{},{},{}", s.time, ctrl.join(","), s.qpos, s.qvel, s.qacc)?;
+    }
+    out.flush()
+}
+
+/// Two point masses on the body with the hinge, `distance` to each side of
+/// the axis, like the weights of a real inertia test; returns the inertia
+/// they add about the axis
+pub fn add_masses(root: &mut Element, mass: f64, distance: f64) -> Result<f64, Box<dyn Error>> {
+    const RADIUS: f64 = 0.02;
+    let mut added = false;
+    root.walk_mut(&mut |body| {
+        let Some(joint) = body.children.iter().find(|e| e.name == "joint" && e.attr("type").is_none_or(|t| t == "hinge")) else {
+            return;
+        };
+        let pos = parse3(joint.attr("pos").unwrap_or("0 0 0"));
+        let axis = unit(&parse3(joint.attr("axis").unwrap_or("0 0 1")));
+        // across the axis, along x unless the axis is
+        let across = if axis[0].abs() > 0.9 { [0.0, 1.0, 0.0] } else { [1.0, 0.0, 0.0] };
+        let across = unit(&cross(&axis, &across));
+        for (i, side) in [1.0, -1.0].into_iter().enumerate() {
+            let mut geom = Element::new("geom");
+            geom.set_attr("name", &format!("calibration_mass{}", i + 1));
+            geom.set_attr("type", "sphere");
+            geom.set_attr("size", &RADIUS.to_string());
+            geom.set_attr("mass", &mass.to_string());
+            let p: Vec<String> = (0..3).map(|k| (pos[k] + side * distance * across[k]).to_string()).collect();
+            geom.set_attr("pos", &p.join(" "));
+            geom.set_attr("contype", "0");
+            geom.set_attr("conaffinity", "0");
+            geom.set_attr("rgba", "0.9 0.7 0.1 1");
+            body.children.push(geom);
+        }
+        added = true;
+    });
+    if !added {
+        return Err("no body with a hinge joint to add the masses to".into());
+    }
+    // parallel axis & a solid sphere each
+    Ok(2.0 * mass * (distance * distance + 0.4 * RADIUS * RADIUS))
+}
+
+fn parse3(s: &str) -> [f64; 3] {
+    let mut v = s.split_whitespace().map(|x| x.parse().unwrap_or(0.0));
+    [v.next().unwrap_or(0.0), v.next().unwrap_or(0.0), v.next().unwrap_or(0.0)]
+}
+
+unsafe fn read3(p: *const f64) -> [f64; 3] {
+    unsafe { [*p, *p.add(1), *p.add(2)] }
+}
+
+fn mat_vec(m: &[f64], v: &[f64; 3]) -> [f64; 3] {
+    [
+        m[0] * v[0] + m[1] * v[1] + m[2] * v[2],
+        m[3] * v[0] + m[4] * v[1] + m[5] * v[2],
+        m[6] * v[0] + m[7] * v[1] + m[8] * v[2],
+    ]
+}
+
+fn cross(a: &[f64; 3], b: &[f64; 3]) -> [f64; 3] {
+    [a[1] * b[2] - a[2] * b[1], a[2] * b[0] - a[0] * b[2], a[0] * b[1] - a[1] * b[0]]
+}
+
+fn dot(a: &[f64; 3], b: &[f64; 3]) -> f64 {
+    a[0] * b[0] + a[1] * b[1] + a[2] * b[2]
+}
+
+fn norm(a: &[f64; 3]) -> f64 {
+    dot(a, a).sqrt()
+}
+
+fn unit(a: &[f64; 3]) -> [f64; 3] {
+    let n = norm(a).max(1e-12);
+    [a[0] / n, a[1] / n, a[2] / n]
+}
@@ -0,0 +1,162 @@
+// actors.rs
+use mujoco_rust::model::ObjType;
+use mujoco_rust::Simulation;
+
+/// How the waypoints are joined
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Interpolation {
+    Linear,
+    CatmullRom, // passes through every waypoint with a smooth heading
+}
+
+/// What happens at the end of the route
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Repeat {
+    Loop,     // back to the first waypoint, the route is closed
+    PingPong, // turn around and drive back
+}
+
+/// A path through waypoints driven at constant speed
+#[derive(Debug, Clone)]
+pub struct Route {
+    pub speed: f64, // m/s
+    pub repeat: Repeat,
+    points: Vec<[f64; 3]>, // polyline, the splines are sampled
+    length: Vec<f64>,      // distance along the route at each point
+}
+
+impl Route {
+    pub fn new(waypoints: &[[f64; 3]], interpolation: Interpolation, speed: f64, repeat: Repeat) -> Self {
+        let mut points = waypoints.to_vec();
+        if repeat == Repeat::Loop && points.len() > 1 {
+            points.push(points[0]);
+        }
+        if interpolation == Interpolation::CatmullRom && points.len() > 2 {
+            points = catmull_rom(&points, repeat == Repeat::Loop, 16);
+        }
+        let mut length = vec![0.0];
+        for pair in points.windows(2) {
+            let d = ((pair[1][0] - pair[0][0]).powi(2) + (pair[1][1] - pair[0][1]).powi(2) + (pair[1][2] - pair[0][2]).powi(2)).sqrt();
+            length.push(length.last().unwrap() + d);
+        }
+        Route { speed, repeat, points, length }
+    }
+
+    pub fn total_length(&self) -> f64 {
+        *self.length.last().unwrap_or(&0.0)
+    }
+
+    /// Position and heading (rad about z) after `distance` along the route
+    pub fn pose(&self, distance: f64) -> ([f64; 3], f64) {
+        let total = self.total_length();
+        if self.points.len() < 2 || total <= 0.0 {
+            return (self.points.first().copied().unwrap_or([0.0; 3]), 0.0);
+        }
+        let (s, backwards) = match self.repeat {
+            Repeat::Loop => (distance.rem_euclid(total), false),
+            Repeat::PingPong => {
+                let s = distance.rem_euclid(2.0 * total);
+                if s > total { (2.0 * total - s, true) } else { (s, false) }
+            }
+        };
+        let i = self.length.partition_point(|&l| l <= s).clamp(1, self.points.len() - 1);
+        let (a, b) = (self.points[i - 1], self.points[i]);
+        let t = ((s - self.length[i - 1]) / (self.length[i] - self.length[i - 1]).max(1e-12)).clamp(0.0, 1.0);
+        let pos = [a[0] + (b[0] - a[0]) * t, a[1] + (b[1] - a[1]) * t, a[2] + (b[2] - a[2]) * t];
+        let mut yaw = (b[1] - a[1]).atan2(b[0] - a[0]);
+        if backwards {
+            yaw += std::f64::consts::PI;
+        }
+        (pos, yaw)
+    }
+}
+
+/// Uniform Catmull-Rom spline through the points, `samples` per
+/// segment; the end points are repeated on an open route
+fn catmull_rom(points: &[[f64; 3]], closed: bool, samples: usize) -> Vec<[f64; 3]> {
+    let n = points.len();
+    let at = |i: isize| -> [f64; 3] {
+        if closed {
+            // the last point is the first one again
+            points[i.rem_euclid(n as isize - 1) as usize]
+        } else {
+            points[i.clamp(0, n as isize - 1) as usize]
+        }
+    };
+    let mut out = Vec::with_capacity((n - 1) * samples + 1);
+    for seg in 0..n - 1 {
+        let (p0, p1, p2, p3) = (at(seg as isize - 1), at(seg as isize), at(seg as isize + 1), at(seg as isize + 2));
+        for k in 0..samples {
+            let t = k as f64 / samples as f64;
+            let (t2, t3) = (t * t, t * t * t);
+            out.push(std::array::from_fn(|j| {
+                0.5 * (2.0 * p1[j] + (p2[j] - p0[j]) * t + (2.0 * p0[j] - 5.0 * p1[j] + 4.0 * p2[j] - p3[j]) * t2 + (3.0 * p1[j] - p0[j] - 3.0 * p2[j] + p3[j]) * t3)
+            }));
+        }
+    }
+    out.push(points[n - 1]);
+    out
+}
+
+/// A mocap body driven along a route
+#[derive(Debug, Clone)]
+pub struct Actor {
+    pub name: String,
+    pub route: Route,
+    pub offset: f64, // m along the route at time 0
+    mocap: usize,
+}
+
+/// Scripted actors, every `update` puts them where their route says for
+/// the simulation time, so a reset or a restored snapshot moves them too
+#[derive(Debug, Clone, Default)]
+pub struct Actors {
+    pub actors: Vec<Actor>,
+}
+
+impl Actors {
+    /// Drive the body `name` along `route`, false when the model has no
+    /// mocap body of that name
+    pub fn add(&mut self, simulation: &Simulation, name: &str, route: Route, offset: f64) -> bool {
+        let Some(body) = simulation.model.name_to_id(ObjType::BODY, name) else {
+            return false;
+        };
+        let mocap = unsafe { *(*simulation.model.ptr()).body_mocapid.add(body as usize) };
+        if mocap < 0 {
+            eprintln!("{} is not a mocap body", name);
+            return false;
+        }
+        self.actors.push(Actor { name: name.to_string(), route, offset, mocap: mocap as usize });
+        true
+    }
+
+    /// Write `mocap_pos`/`mocap_quat` of all actors, call before every
+    /// `simulation.step()`
+    pub fn update(&self, simulation: &Simulation) {
+        unsafe {
+            let mj_data = &*simulation.state.ptr();
+            for actor in self.actors.iter() {
+                let (pos, yaw) = actor.route.pose(actor.offset + actor.route.speed * mj_data.time);
+                let quat = [(yaw / 2.0).cos(), 0.0, 0.0, (yaw / 2.0).sin()];
+                std::slice::from_raw_parts_mut(mj_data.mocap_pos.add(3 * actor.mocap), 3).copy_from_slice(&pos);
+                std::slice::from_raw_parts_mut(mj_data.mocap_quat.add(4 * actor.mocap), 4).copy_from_slice(&quat);
+            }
+        }
+    }
+}
+
+/// The vehicles of `street_scene.xml`, empty for scenes without them
+pub fn street_traffic(simulation: &Simulation) -> Actors {
+    let mut actors = Actors::default();
+    // around the block between road_h1, road_v2, road_h2 and road_v1, in
+    // the inner lane, starting at the far corner from the drone
+    let block = [[1.25, 1.25, 0.5], [23.75, 1.25, 0.5], [23.75, 23.75, 0.5], [1.25, 23.75, 0.5]];
+    actors.add(simulation, "vehicle1", Route::new(&block, Interpolation::Linear, 6.0, Repeat::Loop), 45.0);
+    // up and down road_h2
+    let street = [[-30.0, 26.25, 0.5], [60.0, 26.25, 0.5]];
+    actors.add(simulation, "vehicle2", Route::new(&street, Interpolation::Linear, 10.0, Repeat::PingPong), 0.0);
+    // up road_v2 and a smooth right turn into road_h2
+    let turn = [[26.25, -30.0, 0.5], [26.25, 15.0, 0.5], [31.25, 23.75, 0.5], [60.0, 23.75, 0.5]];
+    actors.add(simulation, "vehicle3", Route::new(&turn, Interpolation::CatmullRom, 7.0, Repeat::PingPong), 0.0);
+    actors
+}
@@ -0,0 +1,252 @@
+// compose.rs
+use crate::mjcf::{self, Element};
+use std::error::Error;
+use std::path::{Path, PathBuf};
+
+// sections that are the same for every instance of a drone file, written once
+const SHARED: [&str; 8] = ["compiler", "option", "size", "visual", "statistic", "default", "asset", "custom"];
+
+// attributes that name an element or refer to one by name; classes, meshes,
+// materials & textures are shared and keep their names
+const NAMES: [&str; 24] = [
+    "name", "site", "site1", "site2", "target", "objname", "refname", "joint", "joint1", "joint2", "geom", "geom1", "geom2", "body",
+    "body1", "body2", "actuator", "tendon", "tendon1", "tendon2", "camera", "jointinparent", "slidersite", "cranksite",
+];
+
+/// How a drone is attached to the world
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Mount {
+    Free,  // the free joint of the drone file
+    Fixed, // no joint, welded where it is placed
+    Axes { slide: [bool; 3], hinge: [bool; 3] }, // only these degrees of freedom, about the x y z of the world
+}
+
+impl Mount {
+    /// Rotation about a single axis (0 = x, 1 = y, 2 = z), like the
+    /// `*_axis_scene.xml` rigs
+    pub fn hinge(axis: usize) -> Self {
+        let mut hinge = [false; 3];
+        hinge[axis] = true;
+        Mount::Axes { slide: [false; 3], hinge }
+    }
+
+    /// Translation along a single axis
+    pub fn slide(axis: usize) -> Self {
+        let mut slide = [false; 3];
+        slide[axis] = true;
+        Mount::Axes { slide, hinge: [false; 3] }
+    }
+}
+
+/// One drone MJCF to attach, e.g. `x2.xml`
+#[derive(Debug, Clone)]
+pub struct Drone {
+    pub file: PathBuf,
+    pub prefix: String,        // before every name, e.g. `d1_` gives `d1_x2`, `d1_thrust1`, `d1_body_gyro`
+    pub pos: Option<[f64; 3]>, // of the top bodies, as in the file if None
+    pub mount: Mount,
+}
+
+impl Drone {
+    pub fn new(file: impl Into<PathBuf>) -> Self {
+        Drone { file: file.into(), prefix: String::new(), pos: None, mount: Mount::Free }
+    }
+}
+
+/// A world MJCF with drones attached, written as one self-contained file
+///
+/// Includes are inlined and asset files get absolute paths, so the result
+/// loads from any folder. An include of a drone file in the world (e.g.
+/// `<include file="x2.xml"/>` of the scenes) is dropped, the drone comes in
+/// through `drones` instead. Defaults and assets of a drone file are written
+/// once however many instances there are; bodies, actuators, sensors,
+/// contacts, equalities and tendons once per instance with its prefix.
+/// Keyframes of a drone only fit a single free drone in a world without
+/// joints, otherwise they are dropped.
+pub struct Composer {
+    pub world: PathBuf,
+    pub drones: Vec<Drone>,
+}
+
+impl Composer {
+    pub fn new(world: impl Into<PathBuf>) -> Self {
+        Composer { world: world.into(), drones: Vec::new() }
+    }
+
+    pub fn compose(&self) -> Result<Element, Box<dyn Error>> {
+        let skip: Vec<PathBuf> = self.drones.iter().filter_map(|d| d.file.canonicalize().ok()).collect();
+        let world = load(&self.world, &skip)?;
+        let mut world_joints = 0;
+        world.walk(&mut |e| {
+            if e.name == "joint" || e.name == "freejoint" {
+                world_joints += 1;
+            }
+        });
+
+        let mut root = Element::new("mujoco");
+        let model = world.attr("model").unwrap_or("world").to_string();
+        root.set_attr("model", &format!("{} + {} drone(s)", model, self.drones.len()));
+
+        let mut shared: Vec<Element> = Vec::new();
+        let mut instances: Vec<Element> = Vec::new();
+        let mut loaded: Vec<PathBuf> = Vec::new();
+        for drone in self.drones.iter() {
+            let doc = load(&drone.file, &[])?;
+            let file = drone.file.canonicalize()?;
+            let first = !loaded.contains(&file);
+            loaded.push(file);
+            let keep_keys = self.drones.len() == 1 && drone.mount == Mount::Free && world_joints == 0;
+
+            for mut section in doc.children {
+                if SHARED.contains(&section.name.as_str()) {
+                    if first {
+                        shared.push(section);
+                    }
+                    continue;
+                }
+                if section.name == "keyframe" && !keep_keys {
+                    continue;
+                }
+                if section.name == "worldbody" {
+                    for body in section.children.iter_mut().filter(|e| e.name == "body") {
+                        place(body, drone);
+                    }
+                }
+                if !drone.prefix.is_empty() {
+                    section.walk_mut(&mut |e| prefix(e, &drone.prefix));
+                }
+                instances.push(section);
+            }
+        }
+
+        // the defaults of the drones come first, as with an include at the top
+        root.children.extend(shared);
+        root.children.extend(world.children);
+        root.children.extend(instances);
+        Ok(root)
+    }
+
+    pub fn write(&self, path: &Path) -> Result<(), Box<dyn Error>> {
+        std::fs::write(path, self.compose()?.to_string())?;
+        Ok(())
+    }
+
+    /// Write into the temp folder, a new file per process
+    pub fn write_temp(&self) -> Result<PathBuf, Box<dyn Error>> {
+        let stem = self.world.file_stem().map_or("world".into(), |s| s.to_string_lossy());
+        let path = std::env::temp_dir().join(format!("{}_composed_{}.xml", stem, std::process::id()));
+        self.write(&path)?;
+        Ok(path)
+    }
+
+    /// Compose, write to the temp folder and load
+    pub fn load(&self) -> Result<mujoco_rust::Model, Box<dyn Error>> {
+        let path = self.write_temp()?;
+        mujoco_rust::Model::from_xml(path.to_string_lossy().to_string()).map_err(|e| format!("{}: {:?}", path.display(), e).into())
+    }
+}
+
+/// Parse an MJCF file with its includes inlined and absolute asset paths;
+/// like MuJoCo, includes and asset folders are relative to the file
+fn load(path: &Path, skip: &[PathBuf]) -> Result<Element, Box<dyn Error>> {
+    let path = path.canonicalize().map_err(|e| format!("{}: {}", path.display(), e))?;
+    let base = path.parent().unwrap_or(Path::new("/")).to_path_buf();
+    let mut root = mjcf::parse(&std::fs::read_to_string(&path)?).map_err(|e| format!("{}: {}", path.display(), e))?;
+    inline(&mut root, &base, skip)?;
+
+    // asset folders of the compiler, the last one set wins
+    let (mut assetdir, mut meshdir, mut texturedir) = (None, None, None);
+    root.walk_mut(&mut |e| {
+        if e.name == "compiler" {
+            for (key, dir) in [("assetdir", &mut assetdir), ("meshdir", &mut meshdir), ("texturedir", &mut texturedir)] {
+                if let Some(v) = e.attr(key) {
+                    *dir = Some(v.to_string());
+                }
+                e.remove_attr(key);
+            }
+        }
+    });
+    root.walk_mut(&mut |e| {
+        // MuJoCo names unnamed assets after the file, keep that name
+        if let (None, Some(file)) = (e.attr("name"), e.attr("file"))
+            && let Some(stem) = Path::new(file).file_stem()
+        {
+            let stem = stem.to_string_lossy().into_owned();
+            e.set_attr("name", &stem);
+        }
+        let dir = match e.name.as_str() {
+            "mesh" => meshdir.as_ref().or(assetdir.as_ref()),
+            "texture" => texturedir.as_ref().or(assetdir.as_ref()),
+            "hfield" | "skin" => assetdir.as_ref(),
+            _ => return,
+        };
+        let folder = base.join(dir.map_or("", |d| d.as_str()));
+        for (key, value) in e.attrs.iter_mut() {
+            if key.starts_with("file") {
+                *value = folder.join(&*value).to_string_lossy().into_owned();
+            }
+        }
+    });
+    Ok(root)
+}
+
+/// Replace `<include>` elements by the children of the root of the file
+fn inline(element: &mut Element, base: &Path, skip: &[PathBuf]) -> Result<(), Box<dyn Error>> {
+    let mut children = Vec::with_capacity(element.children.len());
+    for mut child in std::mem::take(&mut element.children) {
+        if child.name != "include" {
+            inline(&mut child, base, skip)?;
+            children.push(child);
+            continue;
+        }
+        let file = base.join(child.attr("file").ok_or("<include> without file")?);
+        let file = file.canonicalize().map_err(|e| format!("{}: {}", file.display(), e))?;
+        if skip.contains(&file) {
+            continue;
+        }
+        let mut included = mjcf::parse(&std::fs::read_to_string(&file)?).map_err(|e| format!("{}: {}", file.display(), e))?;
+        inline(&mut included, base, skip)?;
+        children.extend(included.children);
+    }
+    element.children = children;
+    Ok(())
+}
+
+/// Position and joints of a top body of a drone
+fn place(body: &mut Element, drone: &Drone) {
+    if let Some([x, y, z]) = drone.pos {
+        body.set_attr("pos", &format!("{} {} {}", x, y, z));
+    }
+    let Mount::Axes { slide, hinge } = drone.mount else {
+        if drone.mount == Mount::Fixed {
+            body.children.retain(|e| !is_free_joint(e));
+        }
+        return;
+    };
+    body.children.retain(|e| !is_free_joint(e));
+    let mut joints = Vec::new();
+    for (kind, enabled) in [("slide", slide), ("hinge", hinge)] {
+        for (axis, label) in ["x", "y", "z"].iter().enumerate() {
+            if enabled[axis] {
+                let mut joint = Element::new("joint");
+                joint.set_attr("name", &format!("{}_{}", kind, label));
+                joint.set_attr("type", kind);
+                joint.set_attr("axis", ["1 0 0", "0 1 0", "0 0 1"][axis]);
+                joints.push(joint);
+            }
+        }
+    }
+    body.children.splice(0..0, joints);
+}
+
+fn is_free_joint(e: &Element) -> bool {
+    e.name == "freejoint" || (e.name == "joint" && e.attr("type") == Some("free"))
+}
+
+fn prefix(e: &mut Element, prefix: &str) {
+    for (key, value) in e.attrs.iter_mut() {
+        if NAMES.contains(&key.as_str()) {
+            *value = format!("{}{}", prefix, value);
+        }
+    }
+}
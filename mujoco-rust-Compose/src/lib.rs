@@ -0,0 +1,8 @@
+// lib.rs
+// MJCF reader & writer, the composition of worlds with drones, the
+// scripted actors of the street scene and seeded random numbers, shared by
+// the demos
+pub mod mjcf;
+pub mod compose;
+pub mod actors;
+pub mod rng;
@@ -0,0 +1,250 @@
+// mjcf.rs
+use std::error::Error;
+use std::fmt;
+
+/// An XML element of an MJCF file, enough to move elements between files
+/// and rename things; comments and text are dropped
+#[derive(Debug, Clone, PartialEq)]
+pub struct Element {
+    pub name: String,
+    pub attrs: Vec<(String, String)>, // in file order, values as written (entities are kept)
+    pub children: Vec<Element>,
+}
+
+impl Element {
+    pub fn new(name: &str) -> Self {
+        Element { name: name.to_string(), attrs: Vec::new(), children: Vec::new() }
+    }
+
+    pub fn attr(&self, key: &str) -> Option<&str> {
+        self.attrs.iter().find(|(k, _)| k == key).map(|(_, v)| v.as_str())
+    }
+
+    /// Replace the value of an attribute, or add it at the end
+    pub fn set_attr(&mut self, key: &str, value: &str) {
+        match self.attrs.iter_mut().find(|(k, _)| k == key) {
+            Some((_, v)) => *v = value.to_string(),
+            None => self.attrs.push((key.to_string(), value.to_string())),
+        }
+    }
+
+    pub fn remove_attr(&mut self, key: &str) {
+        self.attrs.retain(|(k, _)| k != key);
+    }
+
+    /// This element and all below it, depth first
+    pub fn walk_mut(&mut self, f: &mut dyn FnMut(&mut Element)) {
+        f(self);
+        for child in self.children.iter_mut() {
+            child.walk_mut(f);
+        }
+    }
+
+    pub fn walk(&self, f: &mut dyn FnMut(&Element)) {
+        f(self);
+        for child in self.children.iter() {
+            child.walk(f);
+        }
+    }
+
+    /// Indented XML, two spaces per level like the files in `x2/`
+    pub fn write(&self, out: &mut String, depth: usize) {
+        let indent = "  ".repeat(depth);
+        out.push_str(&indent);
+        out.push('<');
+        out.push_str(&self.name);
+        for (k, v) in self.attrs.iter() {
+            out.push_str(&format!(" {}=\"{}\"", k, v.replace('"', "&quot;")));
+        }
+        if self.children.is_empty() {
+            out.push_str("/>\n");
+            return;
+        }
+        out.push_str(">\n");
+        for child in self.children.iter() {
+            child.write(out, depth + 1);
+        }
+        out.push_str(&format!("{}</{}>\n", indent, self.name));
+    }
+}
+
+impl fmt::Display for Element {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut out = String::new();
+        self.write(&mut out, 0);
+        f.write_str(&out)
+    }
+}
+
+#[derive(Debug)]
+pub struct ParseError {
+    pub offset: usize,
+    pub message: String,
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "XML error at byte {}: {}", self.offset, self.message)
+    }
+}
+
+impl Error for ParseError {}
+
+/// Parse the root element of an XML document
+pub fn parse(xml: &str) -> Result<Element, ParseError> {
+    let mut parser = Parser { xml, pos: 0 };
+    parser.skip_misc()?;
+    let root = parser.element()?;
+    parser.skip_misc()?;
+    if parser.pos < xml.len() {
+        return Err(parser.error("content after the root element"));
+    }
+    Ok(root)
+}
+
+struct Parser<'a> {
+    xml: &'a str,
+    pos: usize,
+}
+
+impl Parser<'_> {
+    fn error(&self, message: &str) -> ParseError {
+        ParseError { offset: self.pos, message: message.to_string() }
+    }
+
+    fn rest(&self) -> &str {
+        &self.xml[self.pos..]
+    }
+
+    fn skip_whitespace(&mut self) {
+        let rest = self.rest();
+        self.pos += rest.len() - rest.trim_start().len();
+    }
+
+    /// Skip to after `end`
+    fn skip_past(&mut self, end: &str) -> Result<(), ParseError> {
+        match self.rest().find(end) {
+            Some(i) => {
+                self.pos += i + end.len();
+                Ok(())
+            }
+            None => Err(self.error(&format!("missing {}", end))),
+        }
+    }
+
+    /// Whitespace, comments, the XML declaration & doctype
+    fn skip_misc(&mut self) -> Result<(), ParseError> {
+        loop {
+            self.skip_whitespace();
+            if self.rest().starts_with("<!--") {
+                self.skip_past("-->")?;
+            } else if self.rest().starts_with("<?") {
+                self.skip_past("?>")?;
+            } else if self.rest().starts_with("<!") {
+                self.skip_past(">")?;
+            } else {
+                return Ok(());
+            }
+        }
+    }
+
+    fn name(&mut self) -> Result<String, ParseError> {
+        let rest = self.rest();
+        let len = rest.find(|c: char| c.is_whitespace() || c == '/' || c == '>' || c == '=').unwrap_or(rest.len());
+        if len == 0 {
+            return Err(self.error("expected a name"));
+        }
+        let name = rest[..len].to_string();
+        self.pos += len;
+        Ok(name)
+    }
+
+    fn element(&mut self) -> Result<Element, ParseError> {
+        if !self.rest().starts_with('<') {
+            return Err(self.error("expected an element"));
+        }
+        self.pos += 1;
+        let mut element = Element::new(&self.name()?);
+
+        // attributes up to > or />
+        loop {
+            self.skip_whitespace();
+            if self.rest().starts_with("/>") {
+                self.pos += 2;
+                return Ok(element);
+            }
+            if self.rest().starts_with('>') {
+                self.pos += 1;
+                break;
+            }
+            let key = self.name()?;
+            self.skip_whitespace();
+            if !self.rest().starts_with('=') {
+                return Err(self.error("expected = after the attribute name"));
+            }
+            self.pos += 1;
+            self.skip_whitespace();
+            let quote = self.rest().chars().next().filter(|&c| c == '"' || c == '\'').ok_or_else(|| self.error("expected a quoted value"))?;
+            self.pos += 1;
+            let end = self.rest().find(quote).ok_or_else(|| self.error("unterminated value"))?;
+            element.attrs.push((key, self.rest()[..end].to_string()));
+            self.pos += end + 1;
+        }
+
+        // children up to the closing tag, text is skipped
+        loop {
+            self.skip_misc()?;
+            if self.rest().starts_with("</") {
+                self.pos += 2;
+                let name = self.name()?;
+                if name != element.name {
+                    return Err(self.error(&format!("</{}> closes <{}>", name, element.name)));
+                }
+                self.skip_past(">")?;
+                return Ok(element);
+            }
+            if self.rest().starts_with('<') {
+                element.children.push(self.element()?);
+            } else if self.rest().is_empty() {
+                return Err(self.error(&format!("<{}> is not closed", element.name)));
+            } else {
+                let text = self.rest().find('<').unwrap_or(self.rest().len());
+                self.pos += text;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn x2_round_trip() {
+        let path = concat!(env!("CARGO_MANIFEST_DIR"), "/../x2/x2.xml");
+        let xml = std::fs::read_to_string(path).unwrap();
+        let root = parse(&xml).unwrap();
+        assert_eq!(root.name, "mujoco");
+        assert_eq!(root.attr("model"), Some("Skydio X2"));
+        let written = root.to_string();
+        assert_eq!(parse(&written).unwrap(), root);
+        // written once more, the text does not change either
+        assert_eq!(parse(&written).unwrap().to_string(), written);
+    }
+
+    #[test]
+    fn comments_declaration_and_entities() {
+        let xml = "<?xml version=\"1.0\"?>\n<!-- a comment -->\n<mujoco model='a &amp; b'>\n  text <!-- inside -->\n  <body name=\"x\"><geom size=\".1\"/></body>\n</mujoco>\n";
+        let root = parse(xml).unwrap();
+        assert_eq!(root.attr("model"), Some("a &amp; b")); // entities are kept
+        assert_eq!(root.children.len(), 1);
+        assert_eq!(root.children[0].children[0].attr("size"), Some(".1"));
+        assert_eq!(parse(&root.to_string()).unwrap(), root);
+    }
+
+    #[test]
+    fn unclosed_element() {
+        let error = parse("<mujoco><body></mujoco>").unwrap_err();
+        assert!(error.message.contains("closes"), "{}", error);
+    }
+}
@@ -0,0 +1,61 @@
+// rng.rs
+
+/// Seeded random numbers, the same seed gives the same scene or sensor noise
+/// so runs can be replayed (xorshift64*)
+#[derive(Debug, Clone)]
+pub struct Rng {
+    state: u64,
+}
+
+impl Rng {
+    pub fn new(seed: u64) -> Self {
+        // the state must not be 0
+        Rng { state: (seed ^ 0x9E37_79B9_7F4A_7C15) | 1 }
+    }
+
+    pub fn next_u64(&mut self) -> u64 {
+        self.state ^= self.state >> 12;
+        self.state ^= self.state << 25;
+        self.state ^= self.state >> 27;
+        self.state.wrapping_mul(0x2545_F491_4F6C_DD1D)
+    }
+
+    /// Uniform in [0, 1)
+    pub fn uniform(&mut self) -> f64 {
+        (self.next_u64() >> 11) as f64 / (1u64 << 53) as f64
+    }
+
+    /// Uniform in [min, max)
+    pub fn range(&mut self, [min, max]: [f64; 2]) -> f64 {
+        min + (max - min) * self.uniform()
+    }
+
+    /// Normal distribution with mean 0 and standard deviation 1 (Box-Muller)
+    pub fn gaussian(&mut self) -> f64 {
+        let u1 = 1.0 - self.uniform(); // (0, 1], ln(0) is -inf
+        let u2 = self.uniform();
+        (-2.0 * u1.ln()).sqrt() * (2.0 * std::f64::consts::PI * u2).cos()
+    }
+
+    /// Three independent samples of standard deviation `sigma`
+    pub fn gaussian3(&mut self, sigma: f64) -> [f64; 3] {
+        [self.gaussian() * sigma, self.gaussian() * sigma, self.gaussian() * sigma]
+    }
+
+    /// Number of events of a Poisson process with mean `mean` (Knuth), e.g.
+    /// the obstacle counts of an area
+    pub fn poisson(&mut self, mean: f64) -> usize {
+        if mean > 30.0 {
+            // normal approximation, exp(-mean) underflows the product
+            return (mean + self.gaussian() * mean.sqrt()).round().max(0.0) as usize;
+        }
+        let limit = (-mean).exp();
+        let mut product = self.uniform();
+        let mut count = 0;
+        while product > limit {
+            product *= self.uniform();
+            count += 1;
+        }
+        count
+    }
+}
@@ -0,0 +1,128 @@
+// battery.rs
+use std::f64::consts::PI;
+
+// open circuit voltage of a LiPo cell by state of charge
+const CELL_OCV: [(f64, f64); 12] = [
+    (0.0, 3.27),
+    (0.05, 3.61),
+    (0.1, 3.69),
+    (0.2, 3.73),
+    (0.3, 3.77),
+    (0.4, 3.79),
+    (0.5, 3.82),
+    (0.6, 3.87),
+    (0.7, 3.92),
+    (0.8, 3.98),
+    (0.9, 4.06),
+    (1.0, 4.2),
+];
+const CELL_FULL: f64 = 4.2;
+
+/// LiPo pack powering the rotors
+///
+/// The electrical power of a rotor is its ideal hover power from momentum
+/// theory, `T^1.5 / sqrt(2ρA)`, over `efficiency` (propeller figure of merit
+/// times motor & ESC). The pack voltage sags with the current through
+/// `internal_resistance`, and the thrust available scales with the square
+/// of the voltage (rotor speed follows the voltage).
+#[derive(Debug, Clone)]
+pub struct Battery {
+    pub cells: usize,
+    pub capacity: f64,            // Ah
+    pub internal_resistance: f64, // Ohm, whole pack
+    pub efficiency: f64,
+    pub rotor_radius: f64, // m
+    pub density: f64,      // kg/m³
+    pub avionics_power: f64, // W
+    soc: f64,
+    voltage: f64,
+    current: f64,
+    consumed: f64, // Ah
+    energy: f64,   // Wh
+}
+
+impl Battery {
+    /// Full pack, e.g. `Battery::new(4, 4.28)` for the 4S 4280mAh of a Skydio X2
+    pub fn new(cells: usize, capacity: f64) -> Self {
+        Battery {
+            cells,
+            capacity,
+            internal_resistance: 0.015 * cells as f64,
+            efficiency: 0.5,
+            rotor_radius: 0.13,
+            density: 1.225,
+            avionics_power: 10.0,
+            soc: 1.0,
+            voltage: cells as f64 * CELL_FULL,
+            current: 0.0,
+            consumed: 0.0,
+            energy: 0.0,
+        }
+    }
+
+    /// Draw the power for the rotor thrusts (N) over `dt`
+    pub fn update(&mut self, thrusts: &[f64], dt: f64) {
+        let area = PI * self.rotor_radius * self.rotor_radius;
+        let rotors: f64 = thrusts.iter().map(|t| t.max(0.0).powf(1.5) / (2.0 * self.density * area).sqrt()).sum();
+        let power = rotors / self.efficiency + self.avionics_power;
+
+        // terminal voltage V = OCV - R·P/V, past the maximum power the pack
+        // gives OCV/2
+        let ocv = self.open_circuit_voltage();
+        let discriminant = ocv * ocv - 4.0 * self.internal_resistance * power;
+        self.voltage = if self.soc <= 0.0 {
+            0.0
+        } else {
+            0.5 * (ocv + discriminant.max(0.0).sqrt())
+        };
+        self.current = if self.voltage > 0.0 { power / self.voltage } else { 0.0 };
+
+        let charge = self.current * dt / 3600.0;
+        self.consumed += charge;
+        self.energy += self.current * self.voltage * dt / 3600.0;
+        self.soc = (self.soc - charge / self.capacity).max(0.0);
+    }
+
+    pub fn open_circuit_voltage(&self) -> f64 {
+        let i = CELL_OCV.iter().position(|&(soc, _)| soc >= self.soc).unwrap_or(CELL_OCV.len() - 1).max(1);
+        let (s0, v0) = CELL_OCV[i - 1];
+        let (s1, v1) = CELL_OCV[i];
+        let cell = v0 + (v1 - v0) * ((self.soc - s0) / (s1 - s0)).clamp(0.0, 1.0);
+        cell * self.cells as f64
+    }
+
+    /// Fraction of the full-battery maximum thrust still available, 0 when
+    /// empty
+    pub fn thrust_limit(&self) -> f64 {
+        (self.voltage / (self.cells as f64 * CELL_FULL)).powi(2).min(1.0)
+    }
+
+    /// State of charge, 0..1
+    pub fn soc(&self) -> f64 {
+        self.soc
+    }
+
+    /// Terminal voltage under load (V)
+    pub fn voltage(&self) -> f64 {
+        self.voltage
+    }
+
+    pub fn current(&self) -> f64 {
+        self.current
+    }
+
+    /// Charge (Ah) and energy (Wh) drawn so far
+    pub fn consumed(&self) -> (f64, f64) {
+        (self.consumed, self.energy)
+    }
+
+    /// Flight time left at the current draw (s)
+    pub fn time_remaining(&self) -> f64 {
+        if self.current > 0.0 { self.soc * self.capacity / self.current * 3600.0 } else { f64::INFINITY }
+    }
+
+    /// Full again, e.g. after a reset of the simulation
+    pub fn recharge(&mut self) {
+        *self = Battery { soc: 1.0, current: 0.0, consumed: 0.0, energy: 0.0, voltage: self.cells as f64 * CELL_FULL, ..self.clone() };
+    }
+}
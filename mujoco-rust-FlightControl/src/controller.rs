@@ -0,0 +1,281 @@
+// controller.rs
+use crate::sensors::SensorRegistry;
+use mujoco_rust::Simulation;
+
+/// Where the controller gets the drone state from
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StateSource {
+    GroundTruth, // qpos/qvel of the free joint
+    Sensors,     // attitude from `body_quat`, rates from `imu_gyro` or `body_gyro`
+}
+
+/// State of the drone as seen by the controller
+#[derive(Debug, Clone, Copy, Default)]
+pub struct DroneState {
+    pub pos: [f64; 3],   // world frame
+    pub vel: [f64; 3],   // world frame
+    pub quat: [f64; 4],  // w x y z, body -> world
+    pub omega: [f64; 3], // body frame angular velocity
+}
+
+impl DroneState {
+    pub fn yaw(&self) -> f64 {
+        let [w, x, y, z] = self.quat;
+        (2.0 * (w * z + x * y)).atan2(1.0 - 2.0 * (y * y + z * z))
+    }
+}
+
+/// Read the state of a body with a free joint, e.g. `x2`
+///
+/// There is no position sensor on the x2, so position and velocity always
+/// come from the free joint.
+pub fn read_state(simulation: &Simulation, body: usize, sensors: &SensorRegistry, source: StateSource) -> DroneState {
+    let mut state = DroneState::default();
+    unsafe {
+        let mj_model = &*simulation.model.ptr();
+        let mj_data = &*simulation.state.ptr();
+        let joint = *mj_model.body_jntadr.add(body) as usize;
+        let qpos = mj_data.qpos.add(*mj_model.jnt_qposadr.add(joint) as usize);
+        let qvel = mj_data.qvel.add(*mj_model.jnt_dofadr.add(joint) as usize);
+        for i in 0..3 {
+            state.pos[i] = *qpos.add(i);
+            state.vel[i] = *qvel.add(i);
+            state.omega[i] = *qvel.add(3 + i); // free joint angular velocity is in the body frame
+        }
+        for i in 0..4 {
+            state.quat[i] = *qpos.add(3 + i);
+        }
+    }
+
+    if source == StateSource::Sensors {
+        // the imu site is not rotated, so its frame is the body frame
+        if let Some(quat) = sensors.read4(simulation, "body_quat") {
+            state.quat = quat;
+        }
+        // with the imu error model if there is one
+        if let Some(gyro) = sensors.read3(simulation, "imu_gyro").or_else(|| sensors.read3(simulation, "body_gyro")) {
+            state.omega = gyro;
+        }
+    }
+    state
+}
+
+/// What the controller should track
+#[derive(Debug, Clone, Copy)]
+pub enum Setpoint {
+    Position { pos: [f64; 3], yaw: f64 },
+    Velocity { vel: [f64; 3], yaw: f64 },
+    Attitude { roll: f64, pitch: f64, yaw: f64, thrust: f64 }, // thrust: collective (N)
+}
+
+/// Collective thrust along the body z axis and body frame torques
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Command {
+    pub thrust: f64,
+    pub torque: [f64; 3],
+}
+
+pub struct Pid {
+    pub kp: f64,
+    pub ki: f64,
+    pub kd: f64,
+    pub i_limit: f64, // anti windup, bound of the integral term
+    integral: f64,
+    prev_error: Option<f64>,
+}
+
+impl Pid {
+    pub fn new(kp: f64, ki: f64, kd: f64, i_limit: f64) -> Self {
+        Pid { kp, ki, kd, i_limit, integral: 0.0, prev_error: None }
+    }
+
+    pub fn update(&mut self, error: f64, dt: f64) -> f64 {
+        if self.ki != 0.0 {
+            self.integral = (self.integral + error * dt).clamp(-self.i_limit / self.ki.abs(), self.i_limit / self.ki.abs());
+        }
+        let derivative = self.prev_error.map_or(0.0, |prev| (error - prev) / dt);
+        self.prev_error = Some(error);
+        self.kp * error + self.ki * self.integral + self.kd * derivative
+    }
+
+    pub fn reset(&mut self) {
+        self.integral = 0.0;
+        self.prev_error = None;
+    }
+}
+
+/// Cascaded controller: position -> velocity -> attitude -> body rates
+pub struct Controller {
+    pub mass: f64,
+    pub inertia: [f64; 3], // diagonal, body frame
+    pub gravity: f64,
+    pub pos_p: [f64; 3],
+    pub vel: [Pid; 3],
+    pub att_p: [f64; 3], // roll, pitch, yaw
+    pub rate: [Pid; 3],
+    pub max_speed_xy: f64, // m/s
+    pub max_speed_z: f64,
+    pub max_tilt: f64, // rad
+    pub max_rate: f64, // rad/s
+}
+
+impl Controller {
+    /// Mass & inertia are taken from the body in the model, e.g. `x2`
+    pub fn new(simulation: &Simulation, body: usize) -> Self {
+        let (mass, inertia, gravity) = unsafe {
+            let mj_model = &*simulation.model.ptr();
+            let i = mj_model.body_inertia.add(3 * body);
+            (
+                *mj_model.body_subtreemass.add(body),
+                [*i, *i.add(1), *i.add(2)],
+                -mj_model.opt.gravity[2],
+            )
+        };
+        Controller {
+            mass,
+            inertia,
+            gravity,
+            pos_p: [1.0, 1.0, 1.5],
+            vel: [Pid::new(2.0, 0.4, 0.0, 2.0), Pid::new(2.0, 0.4, 0.0, 2.0), Pid::new(4.0, 1.0, 0.0, 3.0)],
+            att_p: [8.0, 8.0, 3.0],
+            rate: [Pid::new(20.0, 2.0, 0.0, 5.0), Pid::new(20.0, 2.0, 0.0, 5.0), Pid::new(10.0, 1.0, 0.0, 2.0)],
+            max_speed_xy: 2.0,
+            max_speed_z: 1.0,
+            max_tilt: 0.5,
+            max_rate: 4.0,
+        }
+    }
+
+    /// Clear the integrators, e.g. after a reset of the simulation
+    pub fn reset(&mut self) {
+        self.vel.iter_mut().chain(self.rate.iter_mut()).for_each(Pid::reset);
+    }
+
+    pub fn update(&mut self, state: &DroneState, setpoint: &Setpoint, dt: f64) -> Command {
+        let rot = quat_to_mat(&state.quat);
+        let z_body = [rot[2], rot[5], rot[8]];
+
+        // thrust direction (world) & collective thrust
+        let (z_des, yaw, thrust) = match *setpoint {
+            Setpoint::Position { pos, yaw } => {
+                let mut vel = [0.0; 3];
+                for i in 0..3 {
+                    vel[i] = self.pos_p[i] * (pos[i] - state.pos[i]);
+                }
+                let (z_des, thrust) = self.velocity_loop(state, &vel, &z_body, dt);
+                (z_des, yaw, thrust)
+            }
+            Setpoint::Velocity { vel, yaw } => {
+                let (z_des, thrust) = self.velocity_loop(state, &vel, &z_body, dt);
+                (z_des, yaw, thrust)
+            }
+            Setpoint::Attitude { roll, pitch, yaw, thrust } => {
+                let r = euler_to_mat(roll, pitch, yaw);
+                ([r[2], r[5], r[8]], yaw, thrust)
+            }
+        };
+
+        // tilt error: rotation taking the body z axis onto the desired one,
+        // handled apart from yaw so a yaw error never tilts the drone
+        let axis = cross(&z_body, &z_des);
+        let sin = norm(&axis);
+        let angle = sin.atan2(dot(&z_body, &z_des));
+        let mut error = [0.0; 3];
+        if sin > 1e-9 {
+            let world = [axis[0] / sin * angle, axis[1] / sin * angle, axis[2] / sin * angle];
+            error = mat_t_vec(&rot, &world);
+        }
+        error[2] = wrap_angle(yaw - state.yaw());
+
+        // body rates -> angular acceleration -> torque
+        let mut torque = [0.0; 3];
+        let i_omega = [
+            self.inertia[0] * state.omega[0],
+            self.inertia[1] * state.omega[1],
+            self.inertia[2] * state.omega[2],
+        ];
+        let gyroscopic = cross(&state.omega, &i_omega);
+        for i in 0..3 {
+            let rate = (self.att_p[i] * error[i]).clamp(-self.max_rate, self.max_rate);
+            let acc = self.rate[i].update(rate - state.omega[i], dt);
+            torque[i] = self.inertia[i] * acc + gyroscopic[i];
+        }
+        Command { thrust: thrust.max(0.0), torque }
+    }
+
+    // velocity setpoint -> desired thrust direction & collective thrust
+    fn velocity_loop(&mut self, state: &DroneState, vel: &[f64; 3], z_body: &[f64; 3], dt: f64) -> ([f64; 3], f64) {
+        let mut vel = *vel;
+        let speed_xy = (vel[0] * vel[0] + vel[1] * vel[1]).sqrt();
+        if speed_xy > self.max_speed_xy {
+            vel[0] *= self.max_speed_xy / speed_xy;
+            vel[1] *= self.max_speed_xy / speed_xy;
+        }
+        vel[2] = vel[2].clamp(-self.max_speed_z, self.max_speed_z);
+
+        let mut force = [0.0; 3];
+        for i in 0..3 {
+            force[i] = self.mass * self.vel[i].update(vel[i] - state.vel[i], dt);
+        }
+        force[2] = (force[2] + self.mass * self.gravity).max(0.1 * self.mass * self.gravity);
+
+        // keep the tilt below max_tilt, altitude comes first
+        let horizontal = (force[0] * force[0] + force[1] * force[1]).sqrt();
+        let max_horizontal = force[2] * self.max_tilt.tan();
+        if horizontal > max_horizontal {
+            force[0] *= max_horizontal / horizontal;
+            force[1] *= max_horizontal / horizontal;
+        }
+
+        let magnitude = norm(&force);
+        let z_des = [force[0] / magnitude, force[1] / magnitude, force[2] / magnitude];
+        (z_des, dot(&force, z_body))
+    }
+}
+
+// row major rotation matrix of a w x y z quaternion
+pub fn quat_to_mat(q: &[f64; 4]) -> [f64; 9] {
+    let [w, x, y, z] = *q;
+    [
+        1.0 - 2.0 * (y * y + z * z), 2.0 * (x * y - w * z), 2.0 * (x * z + w * y),
+        2.0 * (x * y + w * z), 1.0 - 2.0 * (x * x + z * z), 2.0 * (y * z - w * x),
+        2.0 * (x * z - w * y), 2.0 * (y * z + w * x), 1.0 - 2.0 * (x * x + y * y),
+    ]
+}
+
+// R = Rz(yaw) Ry(pitch) Rx(roll)
+fn euler_to_mat(roll: f64, pitch: f64, yaw: f64) -> [f64; 9] {
+    let (sr, cr) = roll.sin_cos();
+    let (sp, cp) = pitch.sin_cos();
+    let (sy, cy) = yaw.sin_cos();
+    [
+        cy * cp, cy * sp * sr - sy * cr, cy * sp * cr + sy * sr,
+        sy * cp, sy * sp * sr + cy * cr, sy * sp * cr - cy * sr,
+        -sp, cp * sr, cp * cr,
+    ]
+}
+
+fn mat_t_vec(m: &[f64; 9], v: &[f64; 3]) -> [f64; 3] {
+    [
+        m[0] * v[0] + m[3] * v[1] + m[6] * v[2],
+        m[1] * v[0] + m[4] * v[1] + m[7] * v[2],
+        m[2] * v[0] + m[5] * v[1] + m[8] * v[2],
+    ]
+}
+
+pub fn cross(a: &[f64; 3], b: &[f64; 3]) -> [f64; 3] {
+    [a[1] * b[2] - a[2] * b[1], a[2] * b[0] - a[0] * b[2], a[0] * b[1] - a[1] * b[0]]
+}
+
+fn dot(a: &[f64; 3], b: &[f64; 3]) -> f64 {
+    a[0] * b[0] + a[1] * b[1] + a[2] * b[2]
+}
+
+fn norm(a: &[f64; 3]) -> f64 {
+    dot(a, a).sqrt()
+}
+
+fn wrap_angle(angle: f64) -> f64 {
+    let a = (angle + std::f64::consts::PI).rem_euclid(2.0 * std::f64::consts::PI);
+    a - std::f64::consts::PI
+}
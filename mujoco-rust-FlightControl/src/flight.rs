@@ -0,0 +1,355 @@
+// flight.rs
+use crate::controller::StateSource;
+use crate::mission::{phase_done, phase_setpoint, Phase, MISSION};
+use crate::{battery, ground_effect, hil, imu, propulsion, session, synthetic, ui, vehicle, wind};
+use std::path::PathBuf;
+
+const LOW_BATTERY: f64 = 0.15; // state of charge to land at
+const MAX_RPM: f64 = 20000.0;
+
+/// Command line options of a flight with the single x2 of a scene
+pub struct Options {
+    pub source: StateSource,
+    pub start_keyframe: Option<String>,
+    pub save_path: Option<PathBuf>,
+    pub hil_endpoint: Option<String>,
+    pub mock_autopilot: bool,
+    pub seed: u64,
+    pub imu_rate: Option<f64>,
+    pub use_propulsion: bool,
+    pub mean_wind: Option<[f64; 3]>,
+    pub turbulence: Option<f64>,
+    pub gusts: Vec<wind::Gust>,
+    pub use_ground_effect: bool,
+    pub battery_capacity: Option<f64>,
+    pub scene: String,
+}
+
+impl Options {
+    /// The flags given that only apply to a single drone, the swarm flies
+    /// with ideal motors in calm air and without an autopilot
+    pub fn single_drone_flags(&self) -> Vec<&'static str> {
+        let flags = [
+            ("--keyframe", self.start_keyframe.is_some()),
+            ("--save-keyframes", self.save_path.is_some()),
+            ("--hil", self.hil_endpoint.is_some()),
+            ("--mock-autopilot", self.mock_autopilot),
+            ("--imu-rate", self.imu_rate.is_some()),
+            ("--propulsion", self.use_propulsion),
+            ("--wind", self.mean_wind.is_some()),
+            ("--turbulence", self.turbulence.is_some()),
+            ("--gust", !self.gusts.is_empty()),
+            ("--ground-effect", self.use_ground_effect),
+            ("--battery", self.battery_capacity.is_some()),
+        ];
+        flags.into_iter().filter(|&(_, given)| given).map(|(flag, _)| flag).collect()
+    }
+}
+
+/// Fly MISSION, or let an autopilot fly over the HIL link, until the window
+/// is closed
+pub fn fly(options: Options) {
+    let Options {
+        source,
+        start_keyframe,
+        save_path,
+        hil_endpoint,
+        mock_autopilot,
+        seed,
+        imu_rate,
+        use_propulsion,
+        mean_wind,
+        turbulence,
+        gusts,
+        use_ground_effect,
+        battery_capacity,
+        scene,
+    } = options;
+
+    // init model
+    let mut session = session::SimSession::load(&scene);
+    println!("{} keyframes: {}", session.path.display(), session.keyframes().join(", "));
+    if let Some(name) = start_keyframe.as_deref() {
+        if !session.reset_to_keyframe(name) {
+            eprintln!("no keyframe named {}", name);
+        }
+    }
+    let timestep = unsafe { (*session.simulation.model.ptr()).opt.timestep }; // control period
+
+    // physics & imu faster than the control loop
+    let substeps = imu_rate.map_or(1, |rate| imu::substep(&session.simulation, rate));
+    if substeps > 1 {
+        println!("{} physics steps per control step, imu sampled at {:.0}Hz & averaged", substeps, substeps as f64 / timestep);
+    }
+
+    // init ctrl vector
+    let actuator_num = unsafe { (*session.simulation.model.ptr()).nu };
+    let mut ctrl: Vec<f64> = vec![0.0; actuator_num as usize];
+
+    // controller, mixer & sensors of the x2, set up like each drone of --drones
+    let mut vehicle = vehicle::Vehicle::new(&session.simulation, "").expect("no body named x2");
+    let body = vehicle.body;
+    let sensors = &mut vehicle.sensors;
+    // the x2 has no GNSS, barometer or magnetometer in the XML
+    let mut gps = synthetic::Gps::default();
+    gps.fix_loss_rate = 0.01;
+    sensors.add_synthetic("gps", body, synthetic::Synthetic::Gps(gps));
+    sensors.add_synthetic("baro", body, synthetic::Synthetic::Barometer(Default::default()));
+    sensors.add_synthetic("mag", body, synthetic::Synthetic::Magnetometer(Default::default()));
+    // bias, drift, scale factor & misalignment on top of the imu of the model,
+    // the samples of the sub-steps averaged into one reading per control step
+    let mut gyro = imu::Imu::new(sensors.get("body_gyro").unwrap(), imu::ImuErrors::mems_gyro());
+    let mut accel = imu::Imu::new(sensors.get("body_linacc").unwrap(), imu::ImuErrors::mems_accel());
+    gyro.average = substeps;
+    accel.average = substeps;
+    sensors.add_synthetic("imu_gyro", body, synthetic::Synthetic::Imu(gyro));
+    sensors.add_synthetic("imu_accel", body, synthetic::Synthetic::Imu(accel));
+    sensors.seed(seed);
+    sensors.update(&session.simulation);
+    println!("sensors: {}", sensors.names().collect::<Vec<_>>().join(", "));
+    println!("state from {:?}, mass {:.3}kg", source, vehicle.controller.mass);
+    let full_range: Vec<[f64; 2]> = vehicle.mixer.motors.iter().map(|m| m.ctrlrange).collect();
+    println!("allocation matrix:\n{}", vehicle.mixer);
+    if vehicle.mixer.rank() < 4 {
+        println!("thrust, roll, pitch & yaw are not independent, yaw is given up first");
+    }
+
+    // autopilot in the loop instead of the mission
+    let mut hil_link = hil_endpoint.map(|endpoint| {
+        let Some((tcp, port)) = hil::parse_endpoint(&endpoint) else {
+            panic!("--hil expects tcp:PORT or udp:PORT, got {}", endpoint);
+        };
+        if mock_autopilot {
+            hil::spawn_mock_autopilot(tcp, port, 1.0);
+        }
+        let transport = if tcp { hil::Transport::tcp_server(port) } else { hil::Transport::udp(port, None) };
+        hil::HilLink::new(transport.unwrap())
+    });
+    let mut missed = 0; // steps without an answer of the autopilot
+
+    // rotors with spin-up instead of the ideal motors, 10000rpm at hover
+    let mut propulsion = use_propulsion.then(|| propulsion::Propulsion::new(&session.simulation, &vehicle.mixer, MAX_RPM, 0.03));
+
+    // 4S pack, limits the thrust as it drains
+    let mut battery = battery_capacity.map(|mah| battery::Battery::new(4, mah / 1000.0));
+    let substep_dt = timestep / substeps as f64;
+
+    // more thrust near the floor, G switches it on & off
+    let mut ground_effect = ground_effect::GroundEffect::new(&session.simulation, &vehicle.mixer, body);
+    ground_effect.enabled = use_ground_effect;
+
+    // wind on the x2, drawn as a blue arrow (0.2m per m/s) with its drag in red (1m per N)
+    let mut wind = (mean_wind.is_some() || turbulence.is_some() || !gusts.is_empty()).then(|| {
+        let mut wind = wind::Wind::new(&session.simulation, seed);
+        wind.mean = mean_wind.unwrap_or([0.0; 3]);
+        wind.turbulence = turbulence.map(wind::Dryden::new);
+        wind.gusts = gusts;
+        wind
+    });
+
+    // init glfw
+    let mut glfw = glfw::init(glfw::FAIL_ON_ERRORS).unwrap();
+    let mut ui_state = ui::ui_init(&mut glfw, &session.simulation, [0x7FFFFFFF].as_ref()); // 3rd-person perspective
+
+    let mut phase = 0;
+    let mut step = 0;
+    let mut saturated = 0; // steps with ctrl at its limits
+    let mut captures = 0;
+    while !ui_state.window.should_close() {
+        match wind.as_ref() {
+            Some(wind) => {
+                let from = unsafe { std::slice::from_raw_parts((*session.simulation.state.ptr()).xipos.add(3 * body), 3) };
+                let from = [from[0], from[1], from[2]];
+                let arrows = [
+                    ui::Arrow { from, vector: wind.velocity().map(|v| v * 0.2), rgba: [0.2, 0.4, 1.0, 0.8] },
+                    ui::Arrow { from, vector: wind.force(), rgba: [1.0, 0.2, 0.2, 0.8] },
+                ];
+                ui::update_scene_with_arrows(&session.simulation, &mut ui_state, &arrows);
+            }
+            None => ui::update_scene(&session.simulation, &mut ui_state),
+        }
+
+        let state = vehicle.state(&session.simulation, source);
+
+        // the motors give less as the battery voltage sags
+        if let Some(battery) = battery.as_ref() {
+            let limit = battery.thrust_limit();
+            for (motor, range) in vehicle.mixer.motors.iter_mut().zip(full_range.iter()) {
+                motor.ctrlrange[1] = range[0] + (range[1] - range[0]) * limit;
+            }
+            if let Some(propulsion) = propulsion.as_mut() {
+                propulsion.rotors.iter_mut().for_each(|r| r.max_rpm = MAX_RPM * limit.sqrt());
+            }
+        }
+
+        let mut command = None;
+        if let Some(link) = hil_link.as_mut() {
+            // ctrl keeps the last answer, a missed one repeats it
+            match link.exchange(&session.simulation, &state, &vehicle.sensors, &mut ctrl) {
+                Ok(true) => {}
+                Ok(false) => missed += 1,
+                Err(e) => {
+                    eprintln!("autopilot link lost: {}", e);
+                    break;
+                }
+            }
+        } else {
+            // next phase once the current one is done, stay on the last one
+            let done = phase_done(MISSION[phase], &state);
+            let time = unsafe { (*session.simulation.state.ptr()).time };
+            if done && phase + 1 < MISSION.len() {
+                println!("[{:.2}s] {:?} done", time, MISSION[phase]);
+                phase += 1;
+            }
+            // cut the mission short on a low battery
+            if battery.as_ref().is_some_and(|b| b.soc() < LOW_BATTERY) && !matches!(MISSION[phase], Phase::Land) {
+                println!("[{:.2}s] battery low, landing", time);
+                phase = MISSION.len() - 1;
+            }
+
+            let setpoint = phase_setpoint(MISSION[phase], &state);
+            let (c, reduced) = vehicle.control(&state, &setpoint, timestep, &mut ctrl);
+            if reduced {
+                saturated += 1;
+            }
+            command = Some(c);
+        }
+
+        // ideal motors or rotors, near the floor with more thrust
+        let mut applied = ctrl.clone();
+        ground_effect.update(&session.simulation);
+        match propulsion.as_mut() {
+            Some(propulsion) if hil_link.is_some() => {
+                // autopilot outputs as ESC pulses
+                for (rotor, range) in propulsion.rotors.iter_mut().zip(full_range.iter()) {
+                    let throttle = (ctrl[rotor.actuator] - range[0]) / (range[1] - range[0]);
+                    rotor.command = propulsion::RotorCommand::Pwm(1000.0 + 1000.0 * throttle);
+                    applied[rotor.actuator] = 0.0;
+                }
+                ground_effect.scale_rotors(propulsion);
+            }
+            Some(propulsion) => {
+                propulsion.take_thrust(&mut applied);
+                ground_effect.scale_rotors(propulsion);
+            }
+            None => {
+                // the autopilot does not know about the battery limit
+                for motor in vehicle.mixer.motors.iter() {
+                    applied[motor.actuator] = applied[motor.actuator].min(motor.ctrlrange[1]);
+                }
+                ground_effect.scale_ctrl(&mut applied);
+            }
+        }
+        session.simulation.control(&applied);
+
+        // sim forward a control step
+        for _ in 0..substeps {
+            propulsion::clear_applied(&session.simulation);
+            if let Some(propulsion) = propulsion.as_mut() {
+                propulsion.apply(&session.simulation, substep_dt);
+            }
+            if let Some(wind) = wind.as_mut() {
+                wind.apply(&session.simulation, body, substep_dt);
+            }
+            session.simulation.step();
+            vehicle.sensors.update(&session.simulation);
+        }
+        step += 1;
+
+        if let Some(battery) = battery.as_mut() {
+            let thrusts: Vec<f64> = match propulsion.as_ref() {
+                Some(propulsion) => propulsion.rotors.iter().map(|r| r.thrust()).collect(),
+                None => vehicle.mixer.motors.iter().map(|m| applied[m.actuator]).collect(),
+            };
+            battery.update(&thrusts, timestep);
+        }
+
+        if step % 100 == 0 {
+            match command {
+                Some(command) => println!(
+                    "pos [{:.2}, {:.2}, {:.2}] yaw {:.2} thrust {:.2}N ctrl {:.2?} saturated {}/100",
+                    state.pos[0], state.pos[1], state.pos[2], state.yaw(), command.thrust, &ctrl[..4], saturated
+                ),
+                None => println!("pos [{:.2}, {:.2}, {:.2}] ctrl {:.2?} missed {}/100", state.pos[0], state.pos[1], state.pos[2], &ctrl[..4], missed),
+            }
+            if let (Some(gps), Some(baro)) = (vehicle.sensors.read(&session.simulation, "gps"), vehicle.sensors.read(&session.simulation, "baro")) {
+                println!("  gps {:.7}, {:.7} alt {:.2}m fix {} baro {:.2}hPa {:.2}m", gps[0], gps[1], gps[2], gps[6], baro[0], baro[1]);
+            }
+            if ground_effect.enabled {
+                let gains: Vec<String> = ground_effect.gains().iter().map(|g| format!("{:.2}", g)).collect();
+                println!("  ground effect {} at {:.2?}m", gains.join(" "), ground_effect.heights());
+            }
+            if let Some(wind) = wind.as_ref() {
+                let [x, y, z] = wind.velocity();
+                println!("  wind [{:.2}, {:.2}, {:.2}]m/s drag {:.2?}N", x, y, z, wind.force());
+            }
+            if let Some(propulsion) = propulsion.as_ref() {
+                let rpm: Vec<String> = propulsion.rotors.iter().map(|r| format!("{:.0}", r.rpm())).collect();
+                let thrust: f64 = propulsion.rotors.iter().map(|r| r.thrust()).sum();
+                println!("  rotors {} rpm, {:.2}N", rpm.join(" "), thrust);
+            }
+            if let Some(battery) = battery.as_ref() {
+                println!(
+                    "  battery {:.0}% {:.2}V {:.1}A, {:.0}s left, thrust limit {:.0}%",
+                    battery.soc() * 100.0, battery.voltage(), battery.current(), battery.time_remaining(), battery.thrust_limit() * 100.0
+                );
+                if let Some(link) = hil_link.as_mut() {
+                    if let Err(e) = link.send_battery(battery) {
+                        eprintln!("cannot send the battery status: {}", e);
+                    }
+                }
+            }
+            saturated = 0;
+            missed = 0;
+        }
+
+        // Get events in real time
+        glfw.poll_events();
+        for (_, event) in glfw::flush_messages(&ui_state.events) {
+            let glfw::WindowEvent::Key(key, _, glfw::Action::Press, _) = event else {
+                continue;
+            };
+            match key {
+                // restart the mission from the start or the hover keyframe
+                glfw::Key::R | glfw::Key::H => {
+                    match (key, start_keyframe.as_deref()) {
+                        (glfw::Key::H, _) => session.reset_to_keyframe("hover"),
+                        (_, Some(name)) => session.reset_to_keyframe(name),
+                        (_, None) => {
+                            session.simulation.reset();
+                            true
+                        }
+                    };
+                    vehicle.reset(&session.simulation);
+                    if let Some(propulsion) = propulsion.as_mut() {
+                        propulsion.reset();
+                    }
+                    if let Some(battery) = battery.as_mut() {
+                        battery.recharge();
+                    }
+                    phase = 0;
+                }
+                glfw::Key::G => {
+                    ground_effect.enabled = !ground_effect.enabled;
+                    println!("ground effect {}", if ground_effect.enabled { "on" } else { "off" });
+                }
+                // keep the current state as a keyframe
+                glfw::Key::K => {
+                    let name = format!("capture_{}", captures);
+                    captures += 1;
+                    println!("captured {}", session.capture_keyframe(&name).to_xml());
+                }
+                _ => {}
+            }
+        }
+    }
+
+    ui::free_glfw(&mut ui_state);
+
+    if let Some(path) = save_path {
+        match session.write_keyframes(&path) {
+            Ok(()) => println!("keyframes written to {}", path.display()),
+            Err(e) => eprintln!("cannot write keyframes to {}: {}", path.display(), e),
+        }
+    }
+}
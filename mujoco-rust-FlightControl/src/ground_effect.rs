@@ -0,0 +1,107 @@
+// ground_effect.rs
+use crate::mixer::Mixer;
+use crate::propulsion::Propulsion;
+use mujoco_rs_sys::no_render;
+use mujoco_rust::Simulation;
+use std::ptr;
+
+/// Extra thrust of the rotors near a surface (Cheeseman & Bennett)
+///
+/// A ray goes from each thrust site against its thrust direction, the
+/// thrust gain is `1 / (1 - (R / 4z)²)` for a surface at distance `z`,
+/// capped at `max_gain`. The rays only look down the rotor axis, a wall
+/// counts once the drone tilts towards it.
+pub struct GroundEffect {
+    pub enabled: bool,
+    pub rotor_radius: f64, // m
+    pub max_gain: f64,
+    pub range: f64, // no effect beyond, m
+    rotors: Vec<(usize, usize, [f64; 3])>, // actuator, site, unit thrust direction in the mixer body frame
+    body: usize,
+    heights: Vec<f64>, // distance of each rotor to the surface, inf if none in range
+}
+
+impl GroundEffect {
+    /// One ray per motor of the mixer, `body` is the body of the mixer
+    pub fn new(simulation: &Simulation, mixer: &Mixer, body: usize) -> Self {
+        let rotors: Vec<_> = mixer
+            .motors
+            .iter()
+            .map(|motor| {
+                let site = unsafe { *(*simulation.model.ptr()).actuator_trnid.add(2 * motor.actuator) as usize };
+                let f = motor.force;
+                let norm = (f[0] * f[0] + f[1] * f[1] + f[2] * f[2]).sqrt().max(1e-12);
+                (motor.actuator, site, f.map(|v| v / norm))
+            })
+            .collect();
+        let heights = vec![f64::INFINITY; rotors.len()];
+        GroundEffect {
+            enabled: true,
+            rotor_radius: 0.13, // rotor geoms of x2.xml
+            max_gain: 1.4,
+            range: 2.0,
+            rotors,
+            body,
+            heights,
+        }
+    }
+
+    /// Cast the rays from the current poses
+    pub fn update(&mut self, simulation: &Simulation) {
+        unsafe {
+            let m = simulation.model.ptr();
+            let d = simulation.state.ptr();
+            let mj_data = &*d;
+            let xmat = std::slice::from_raw_parts(mj_data.xmat.add(9 * self.body), 9);
+            for (i, &(_, site, dir)) in self.rotors.iter().enumerate() {
+                let pnt = std::slice::from_raw_parts(mj_data.site_xpos.add(3 * site), 3);
+                // against the thrust, in world frame
+                let vec: [f64; 3] = std::array::from_fn(|r| -(xmat[3 * r] * dir[0] + xmat[3 * r + 1] * dir[1] + xmat[3 * r + 2] * dir[2]));
+                let mut geom = -1;
+                let site_body = *(*m).site_bodyid.add(site);
+                let distance = no_render::mj_ray(m, d, pnt.as_ptr(), vec.as_ptr(), ptr::null(), 1, site_body, &mut geom);
+                self.heights[i] = if distance >= 0.0 && distance < self.range { distance } else { f64::INFINITY };
+            }
+        }
+    }
+
+    /// Distance of each rotor to the surface below it
+    pub fn heights(&self) -> &[f64] {
+        &self.heights
+    }
+
+    /// Thrust gain of each rotor, 1 when disabled
+    pub fn gains(&self) -> Vec<f64> {
+        self.heights
+            .iter()
+            .map(|&z| {
+                if !self.enabled || !z.is_finite() {
+                    return 1.0;
+                }
+                let ratio = self.rotor_radius / (4.0 * z.max(1e-6));
+                if ratio * ratio >= 1.0 - 1.0 / self.max_gain {
+                    self.max_gain
+                } else {
+                    1.0 / (1.0 - ratio * ratio)
+                }
+            })
+            .collect()
+    }
+
+    /// Scale the thrust in the ctrl of the ideal motors
+    pub fn scale_ctrl(&self, ctrl: &mut [f64]) {
+        for (&(actuator, _, _), gain) in self.rotors.iter().zip(self.gains()) {
+            ctrl[actuator] *= gain;
+        }
+    }
+
+    /// Scale the thrust of the rotors of a propulsion model
+    pub fn scale_rotors(&self, propulsion: &mut Propulsion) {
+        let gains = self.gains();
+        for rotor in propulsion.rotors.iter_mut() {
+            if let Some(i) = self.rotors.iter().position(|r| r.0 == rotor.actuator) {
+                rotor.thrust_scale = gains[i];
+            }
+        }
+    }
+}
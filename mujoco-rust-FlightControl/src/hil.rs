@@ -0,0 +1,427 @@
+// hil.rs
+use crate::battery::Battery;
+use crate::controller::{quat_to_mat, DroneState};
+use crate::mavlink::{self, BatteryStatus, HilActuatorControls, HilGps, HilSensor, HilStateQuaternion, Message, Parser};
+use crate::sensors::SensorRegistry;
+use crate::synthetic::{pressure, GeoOrigin};
+use mujoco_rust::Simulation;
+use std::io::{self, ErrorKind, Read, Write};
+use std::net::{SocketAddr, TcpListener, TcpStream, UdpSocket};
+use std::thread;
+use std::time::{Duration, Instant};
+
+const GRAVITY: f64 = 9.80665;
+
+/// Byte transport to the autopilot
+pub enum Transport {
+    Tcp(TcpStream),
+    Udp { socket: UdpSocket, peer: Option<SocketAddr> }, // peer is learned from the first packet
+}
+
+impl Transport {
+    /// Listen on the port and wait for the autopilot, PX4 SITL connects to 4560
+    pub fn tcp_server(port: u16) -> io::Result<Self> {
+        let listener = TcpListener::bind(("0.0.0.0", port))?;
+        println!("waiting for the autopilot on tcp port {}", port);
+        let (stream, addr) = listener.accept()?;
+        println!("autopilot connected from {}", addr);
+        stream.set_nodelay(true)?;
+        Ok(Transport::Tcp(stream))
+    }
+
+    pub fn tcp_client(addr: SocketAddr) -> io::Result<Self> {
+        let stream = TcpStream::connect(addr)?;
+        stream.set_nodelay(true)?;
+        Ok(Transport::Tcp(stream))
+    }
+
+    pub fn udp(port: u16, peer: Option<SocketAddr>) -> io::Result<Self> {
+        let socket = UdpSocket::bind(("0.0.0.0", port))?;
+        Ok(Transport::Udp { socket, peer })
+    }
+
+    // false while a udp peer is not known
+    fn connected(&self) -> bool {
+        !matches!(self, Transport::Udp { peer: None, .. })
+    }
+
+    fn send(&mut self, bytes: &[u8]) -> io::Result<()> {
+        match self {
+            Transport::Tcp(stream) => stream.write_all(bytes),
+            Transport::Udp { socket, peer: Some(peer) } => socket.send_to(bytes, *peer).map(|_| ()),
+            Transport::Udp { peer: None, .. } => Ok(()), // nobody to send to yet
+        }
+    }
+
+    // 0 bytes on timeout
+    fn recv(&mut self, buf: &mut [u8], timeout: Duration) -> io::Result<usize> {
+        let result = match self {
+            Transport::Tcp(stream) => {
+                stream.set_read_timeout(Some(timeout))?;
+                match stream.read(buf) {
+                    Ok(0) => Err(io::Error::new(ErrorKind::ConnectionAborted, "autopilot disconnected")),
+                    result => result,
+                }
+            }
+            Transport::Udp { socket, peer } => {
+                socket.set_read_timeout(Some(timeout))?;
+                socket.recv_from(buf).map(|(n, from)| {
+                    peer.get_or_insert(from);
+                    n
+                })
+            }
+        };
+        match result {
+            Err(e) if matches!(e.kind(), ErrorKind::WouldBlock | ErrorKind::TimedOut) => Ok(0),
+            result => result,
+        }
+    }
+}
+
+/// Parse `tcp:4560` or `udp:14560`, the simulator side of the link
+pub fn parse_endpoint(endpoint: &str) -> Option<(bool, u16)> {
+    let (protocol, port) = endpoint.split_once(':')?;
+    let port = port.parse().ok()?;
+    match protocol {
+        "tcp" => Some((true, port)),
+        "udp" => Some((false, port)),
+        _ => None,
+    }
+}
+
+// PX4 quad X motor order (front right, rear left, front left, rear right)
+// to the thrust1..4 actuators of x2.xml
+pub const PX4_QUAD_X: [usize; 4] = [3, 1, 2, 0];
+
+/// Hardware-in-the-loop link to PX4/ArduPilot SITL
+///
+/// Lockstep: each step sends the sensors of the current state and waits
+/// for the HIL_ACTUATOR_CONTROLS answer before stepping the simulation.
+pub struct HilLink {
+    transport: Transport,
+    parser: Parser,
+    seq: u8,
+    pub sysid: u8,
+    pub compid: u8,
+    pub home: GeoOrigin,
+    pub mag_field: [f64; 3], // Earth field, NED (gauss), without a `mag` sensor
+    pub motor_map: [usize; 4], // actuator of each autopilot motor output
+    pub gps_interval: usize,   // steps between HIL_GPS
+    pub timeout: Duration,     // for the actuator answer
+    step: usize,
+}
+
+impl HilLink {
+    pub fn new(transport: Transport) -> Self {
+        HilLink {
+            transport,
+            parser: Parser::default(),
+            seq: 0,
+            sysid: 1,
+            compid: 1,
+            home: GeoOrigin::default(),
+            mag_field: [0.21, 0.015, 0.43],
+            motor_map: PX4_QUAD_X,
+            gps_interval: 10,
+            timeout: Duration::from_secs(5),
+            step: 0,
+        }
+    }
+
+    pub fn send(&mut self, message: &Message) -> io::Result<()> {
+        let frame = mavlink::encode(message, self.seq, self.sysid, self.compid);
+        self.seq = self.seq.wrapping_add(1);
+        self.transport.send(&frame)
+    }
+
+    /// HIL_SENSOR & HIL_STATE_QUATERNION every step, HIL_GPS every `gps_interval`
+    ///
+    /// IMU values come from `imu_gyro` & `imu_accel` if the registry has an
+    /// error model, else from the sensors of the model. Magnetometer,
+    /// barometer and GNSS come from the `mag`, `baro` and `gps` sensors of the
+    /// registry if there are, else from ground truth.
+    pub fn send_state(&mut self, simulation: &Simulation, state: &DroneState, sensors: &SensorRegistry) -> io::Result<()> {
+        let time_usec = unsafe { ((*simulation.state.ptr()).time * 1e6) as u64 };
+        let frame = NedFrame::new(state);
+        // the imu site is not rotated, its frame is the body frame (FLU)
+        let acc = sensors.read3(simulation, "imu_accel").or_else(|| sensors.read3(simulation, "body_linacc"));
+        let gyro = sensors.read3(simulation, "imu_gyro").or_else(|| sensors.read3(simulation, "body_gyro"));
+        let acc = frd(&acc.unwrap_or([0.0, 0.0, GRAVITY]));
+        let gyro = frd(&gyro.unwrap_or(state.omega));
+        let mag = match sensors.read3(simulation, "mag") {
+            Some(mag) => frd(&mag),
+            None => frame.to_body(&self.mag_field),
+        };
+        let (lat, lon, alt) = self.home.to_geodetic(&state.pos);
+        let vel = frame.vel;
+        let (abs_pressure, pressure_alt) = match sensors.read(simulation, "baro") {
+            Some(baro) => (baro[0], baro[1]),
+            None => (pressure(alt), alt),
+        };
+        // lat, lon, alt, NED velocity, fix
+        let gnss = match sensors.read(simulation, "gps") {
+            Some(g) => (g[0], g[1], g[2], [g[4], g[3], -g[5]], g[6] > 0.0),
+            None => (lat, lon, alt, vel, true),
+        };
+
+        let sensor = HilSensor {
+            time_usec,
+            acc: acc.map(|v| v as f32),
+            gyro: gyro.map(|v| v as f32),
+            mag: mag.map(|v| v as f32),
+            abs_pressure: abs_pressure as f32,
+            diff_pressure: 0.0,
+            pressure_alt: pressure_alt as f32,
+            temperature: 20.0,
+            fields_updated: 0x1FFF, // acc, gyro, mag, pressures, altitude & temperature
+            id: 0,
+        };
+        self.send(&Message::HilSensor(sensor))?;
+
+        if self.step % self.gps_interval.max(1) == 0 {
+            let (lat, lon, alt, vel, fix) = gnss;
+            let speed = (vel[0] * vel[0] + vel[1] * vel[1]).sqrt();
+            let gps = HilGps {
+                time_usec,
+                lat: (lat * 1e7) as i32,
+                lon: (lon * 1e7) as i32,
+                alt: (alt * 1000.0) as i32,
+                eph: 100,
+                epv: 100,
+                vel: (speed * 100.0) as u16,
+                vn: (vel[0] * 100.0) as i16,
+                ve: (vel[1] * 100.0) as i16,
+                vd: (vel[2] * 100.0) as i16,
+                cog: (vel[1].atan2(vel[0]).to_degrees().rem_euclid(360.0) * 100.0) as u16 % 36000,
+                fix_type: if fix { 3 } else { 1 }, // 3D fix or none
+                satellites_visible: if fix { 10 } else { 0 },
+                id: 0,
+                yaw: 0,
+            };
+            self.send(&Message::HilGps(gps))?;
+        }
+
+        let rates = frd(&state.omega);
+        let truth = HilStateQuaternion {
+            time_usec,
+            attitude_quaternion: frame.quat.map(|v| v as f32),
+            rollspeed: rates[0] as f32,
+            pitchspeed: rates[1] as f32,
+            yawspeed: rates[2] as f32,
+            lat: (lat * 1e7) as i32,
+            lon: (lon * 1e7) as i32,
+            alt: (alt * 1000.0) as i32,
+            vx: (vel[0] * 100.0) as i16,
+            vy: (vel[1] * 100.0) as i16,
+            vz: (vel[2] * 100.0) as i16,
+            ind_airspeed: ((vel[0] * vel[0] + vel[1] * vel[1] + vel[2] * vel[2]).sqrt() * 100.0) as u16,
+            true_airspeed: ((vel[0] * vel[0] + vel[1] * vel[1] + vel[2] * vel[2]).sqrt() * 100.0) as u16,
+            xacc: (acc[0] / GRAVITY * 1000.0) as i16,
+            yacc: (acc[1] / GRAVITY * 1000.0) as i16,
+            zacc: (acc[2] / GRAVITY * 1000.0) as i16,
+        };
+        self.send(&Message::HilStateQuaternion(truth))?;
+        self.step += 1;
+        Ok(())
+    }
+
+    /// BATTERY_STATUS of the pack, e.g. once a second
+    pub fn send_battery(&mut self, battery: &Battery) -> io::Result<()> {
+        let cell = (battery.voltage() / battery.cells as f64 * 1000.0) as u16;
+        let mut voltages = [u16::MAX; 10];
+        voltages.iter_mut().take(battery.cells).for_each(|v| *v = cell);
+        let (charge, energy) = battery.consumed();
+        let status = BatteryStatus {
+            current_consumed: (charge * 1000.0) as i32,
+            energy_consumed: (energy * 36.0) as i32, // Wh -> hJ
+            temperature: i16::MAX,
+            voltages,
+            current_battery: (battery.current() * 100.0) as i16,
+            battery_type: 1, // LiPo
+            battery_remaining: (battery.soc() * 100.0).round() as i8,
+            time_remaining: battery.time_remaining().min(i32::MAX as f64) as i32,
+            ..Default::default()
+        };
+        self.send(&Message::BatteryStatus(status))
+    }
+
+    /// Block until the autopilot answers with actuator controls, None on timeout
+    pub fn wait_actuators(&mut self) -> io::Result<Option<HilActuatorControls>> {
+        let deadline = Instant::now() + self.timeout;
+        let mut buf = [0u8; 2048];
+        while let Some(left) = deadline.checked_duration_since(Instant::now()) {
+            let n = self.transport.recv(&mut buf, left.max(Duration::from_millis(1)))?;
+            let controls = self.parser.push(&buf[..n]).into_iter().filter_map(|(_, m)| match m {
+                Message::HilActuatorControls(c) => Some(c),
+                _ => None,
+            });
+            if let Some(controls) = controls.last() {
+                return Ok(Some(controls));
+            }
+        }
+        Ok(None)
+    }
+
+    /// Motor outputs (0..1) to ctrl, all motors off while disarmed
+    pub fn apply(&self, simulation: &Simulation, controls: &HilActuatorControls, ctrl: &mut [f64]) {
+        let armed = controls.mode & mavlink::MODE_FLAG_SAFETY_ARMED != 0;
+        for (output, &actuator) in self.motor_map.iter().enumerate() {
+            let [min, max] = unsafe {
+                let range = (*simulation.model.ptr()).actuator_ctrlrange.add(2 * actuator);
+                [*range, *range.add(1)]
+            };
+            let value = if armed { controls.controls[output].clamp(0.0, 1.0) as f64 } else { 0.0 };
+            ctrl[actuator] = min + value * (max - min);
+        }
+    }
+
+    /// One lockstep exchange, false if the autopilot did not answer in time
+    /// (ctrl is left as it was)
+    pub fn exchange(&mut self, simulation: &Simulation, state: &DroneState, sensors: &SensorRegistry, ctrl: &mut [f64]) -> io::Result<bool> {
+        if !self.transport.connected() {
+            // udp: the autopilot has to send first, e.g. a heartbeat
+            let mut buf = [0u8; 2048];
+            let n = self.transport.recv(&mut buf, self.timeout)?;
+            self.parser.push(&buf[..n]);
+            if !self.transport.connected() {
+                return Ok(false);
+            }
+        }
+        self.send_state(simulation, state, sensors)?;
+        match self.wait_actuators()? {
+            Some(controls) => {
+                self.apply(simulation, &controls, ctrl);
+                Ok(true)
+            }
+            None => Ok(false),
+        }
+    }
+}
+
+// attitude & velocity in the NED world / FRD body frames of the autopilots
+struct NedFrame {
+    rot: [f64; 9], // FRD body -> NED, row major
+    quat: [f64; 4],
+    vel: [f64; 3],
+}
+
+impl NedFrame {
+    fn new(state: &DroneState) -> Self {
+        // R_ned_frd = T R_enu_flu F, T swaps x/y & flips z, F flips y & z
+        let r = quat_to_mat(&state.quat);
+        let mut rot = [0.0; 9];
+        for col in 0..3 {
+            let sign = if col == 0 { 1.0 } else { -1.0 };
+            rot[col] = r[3 + col] * sign;
+            rot[3 + col] = r[col] * sign;
+            rot[6 + col] = -r[6 + col] * sign;
+        }
+        NedFrame {
+            rot,
+            quat: mat_to_quat(&rot),
+            vel: [state.vel[1], state.vel[0], -state.vel[2]],
+        }
+    }
+
+    // NED vector in the body frame
+    fn to_body(&self, v: &[f64; 3]) -> [f64; 3] {
+        let r = &self.rot;
+        [
+            r[0] * v[0] + r[3] * v[1] + r[6] * v[2],
+            r[1] * v[0] + r[4] * v[1] + r[7] * v[2],
+            r[2] * v[0] + r[5] * v[1] + r[8] * v[2],
+        ]
+    }
+}
+
+// FLU -> FRD
+fn frd(v: &[f64; 3]) -> [f64; 3] {
+    [v[0], -v[1], -v[2]]
+}
+
+// w x y z of a row major rotation matrix
+fn mat_to_quat(m: &[f64; 9]) -> [f64; 4] {
+    let trace = m[0] + m[4] + m[8];
+    let q = if trace > 0.0 {
+        let s = 0.5 / (trace + 1.0).sqrt();
+        [0.25 / s, (m[7] - m[5]) * s, (m[2] - m[6]) * s, (m[3] - m[1]) * s]
+    } else if m[0] > m[4] && m[0] > m[8] {
+        let s = 2.0 * (1.0 + m[0] - m[4] - m[8]).sqrt();
+        [(m[7] - m[5]) / s, 0.25 * s, (m[1] + m[3]) / s, (m[2] + m[6]) / s]
+    } else if m[4] > m[8] {
+        let s = 2.0 * (1.0 + m[4] - m[0] - m[8]).sqrt();
+        [(m[2] - m[6]) / s, (m[1] + m[3]) / s, 0.25 * s, (m[5] + m[7]) / s]
+    } else {
+        let s = 2.0 * (1.0 + m[8] - m[0] - m[4]).sqrt();
+        [(m[3] - m[1]) / s, (m[2] + m[6]) / s, (m[5] + m[7]) / s, 0.25 * s]
+    };
+    // keep w positive
+    if q[0] < 0.0 { q.map(|v| -v) } else { q }
+}
+
+/// Stand-in for PX4 to test the link: answers every HIL_SENSOR with the
+/// same output on all motors, holding the altitude from HIL_STATE_QUATERNION
+pub fn spawn_mock_autopilot(tcp: bool, port: u16, target_height: f64) -> thread::JoinHandle<io::Result<()>> {
+    thread::spawn(move || {
+        let addr: SocketAddr = ([127, 0, 0, 1], port).into();
+        let mut transport = if tcp {
+            // the simulator may not listen yet
+            let mut attempt = 0;
+            loop {
+                match Transport::tcp_client(addr) {
+                    Ok(t) => break t,
+                    Err(_) if attempt < 50 => {
+                        attempt += 1;
+                        thread::sleep(Duration::from_millis(100));
+                    }
+                    Err(e) => return Err(e),
+                }
+            }
+        } else {
+            Transport::udp(0, Some(addr))?
+        };
+
+        let mut parser = Parser::default();
+        let mut seq = 0u8;
+        let mut send = |transport: &mut Transport, message: Message| {
+            let frame = mavlink::encode(&message, seq, 1, 1);
+            seq = seq.wrapping_add(1);
+            transport.send(&frame)
+        };
+        // lets a udp simulator learn the address
+        send(&mut transport, Message::Heartbeat(mavlink::Heartbeat { mav_type: 2, autopilot: 12, mavlink_version: 3, ..Default::default() }))?;
+
+        let hover = 0.25; // 1.325kg * 9.81 / (4 * 13N)
+        let mut ground: Option<f64> = None;
+        let (mut height, mut climb) = (0.0, 0.0);
+        let mut buf = [0u8; 2048];
+        loop {
+            let n = transport.recv(&mut buf, Duration::from_secs(10))?;
+            if n == 0 {
+                return Ok(()); // simulator gone quiet
+            }
+            for (_, message) in parser.push(&buf[..n]) {
+                match message {
+                    Message::HilStateQuaternion(s) => {
+                        let alt = s.alt as f64 / 1000.0;
+                        height = alt - *ground.get_or_insert(alt);
+                        climb = -s.vz as f64 / 100.0;
+                    }
+                    Message::HilSensor(s) => {
+                        let throttle = (hover + 0.1 * (target_height - height) - 0.1 * climb).clamp(0.0, 1.0) as f32;
+                        let mut controls = [0.0; 16];
+                        controls[..4].fill(throttle);
+                        let answer = HilActuatorControls {
+                            time_usec: s.time_usec,
+                            flags: 0,
+                            controls,
+                            mode: mavlink::MODE_FLAG_SAFETY_ARMED,
+                        };
+                        send(&mut transport, Message::HilActuatorControls(answer))?;
+                    }
+                    _ => {}
+                }
+            }
+        }
+    })
+}
@@ -0,0 +1,123 @@
+// imu.rs
+use crate::sensors::SensorInfo;
+use mujoco_compose::rng::Rng;
+use mujoco_rust::Simulation;
+
+/// Error model of a 3-axis gyro or accelerometer
+///
+/// Bias, scale factor and misalignment are drawn once per run from their
+/// sigmas, the bias then drifts as a random walk and white noise is added to
+/// every sample. Units are those of the sensor (rad/s or m/s²).
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ImuErrors {
+    pub bias: f64,         // turn-on bias sigma
+    pub bias_walk: f64,    // per sqrt(s)
+    pub scale: f64,        // scale factor error sigma, 0.001 is 1000ppm
+    pub misalignment: f64, // axis misalignment sigma (rad)
+    pub noise: f64,        // white noise density, per sqrt(Hz)
+}
+
+impl ImuErrors {
+    /// Typical consumer MEMS gyro
+    pub fn mems_gyro() -> Self {
+        ImuErrors { bias: 0.003, bias_walk: 0.0003, scale: 0.003, misalignment: 0.002, noise: 0.0002 }
+    }
+
+    /// Typical consumer MEMS accelerometer
+    pub fn mems_accel() -> Self {
+        ImuErrors { bias: 0.05, bias_walk: 0.002, scale: 0.005, misalignment: 0.002, noise: 0.002 }
+    }
+}
+
+// errors drawn for this run
+#[derive(Debug, Clone)]
+struct Drawn {
+    bias: [f64; 3],
+    transform: [f64; 9], // misalignment * (I + scale), row major
+}
+
+/// A MuJoCo sensor, e.g. `body_gyro`, seen through an error model
+///
+/// With `average` > 1 the reading is the mean of that many samples and only
+/// changes once they are in, like the filter of an IMU driver. Sampled every
+/// sub-step and averaged over the control step, the white noise of the
+/// reading is that of the control period.
+#[derive(Debug, Clone)]
+pub struct Imu {
+    pub source: SensorInfo,
+    pub errors: ImuErrors,
+    pub average: usize, // samples per reading
+    drawn: Option<Drawn>,
+    last_time: Option<f64>,
+    sum: [f64; 3],
+    count: usize,
+}
+
+impl Imu {
+    pub fn new(source: SensorInfo, errors: ImuErrors) -> Self {
+        Imu { source, errors, average: 1, drawn: None, last_time: None, sum: [0.0; 3], count: 0 }
+    }
+
+    pub fn update(&mut self, simulation: &Simulation, rng: &mut Rng, values: &mut [f64]) {
+        let time = unsafe { (*simulation.state.ptr()).time };
+        let data = simulation.sensordata();
+        let truth = &data[self.source.adr..self.source.adr + 3];
+        let e = self.errors;
+        let drawn = self.drawn.get_or_insert_with(|| {
+            let scale = rng.gaussian3(e.scale);
+            let [rx, ry, rz] = rng.gaussian3(e.misalignment);
+            // small rotation I + [r]x
+            let m = [1.0, -rz, ry, rz, 1.0, -rx, -ry, rx, 1.0];
+            let mut transform = [0.0; 9];
+            for row in 0..3 {
+                for col in 0..3 {
+                    transform[3 * row + col] = m[3 * row + col] * (1.0 + scale[col]);
+                }
+            }
+            Drawn { bias: rng.gaussian3(e.bias), transform }
+        });
+
+        let dt = time - self.last_time.unwrap_or(time);
+        self.last_time = Some(time);
+        let walk = rng.gaussian3(e.bias_walk * dt.max(0.0).sqrt());
+        // white noise of the sample period, no noise before the first period is known
+        let sigma = if dt > 0.0 { e.noise / dt.sqrt() } else { 0.0 };
+        let noise = rng.gaussian3(sigma);
+        let t = &drawn.transform;
+        for i in 0..3 {
+            drawn.bias[i] += walk[i];
+            self.sum[i] += t[3 * i] * truth[0] + t[3 * i + 1] * truth[1] + t[3 * i + 2] * truth[2] + drawn.bias[i] + noise[i];
+        }
+        self.count += 1;
+        if self.count >= self.average.max(1) {
+            for i in 0..3 {
+                values[i] = self.sum[i] / self.count as f64;
+            }
+            self.sum = [0.0; 3];
+            self.count = 0;
+        }
+    }
+
+    /// Draw new errors on the next update
+    pub fn reset(&mut self) {
+        self.drawn = None;
+        self.last_time = None;
+        self.sum = [0.0; 3];
+        self.count = 0;
+    }
+}
+
+/// Run the physics at about `rate` Hz so the IMU is sampled at that rate,
+/// by dividing the timestep. Returns the number of sub-steps per timestep
+/// of the model, step that many times per control step and average that
+/// many samples per reading (`Imu::average`).
+///
+/// The dynamics change slightly with the smaller timestep.
+pub fn substep(simulation: &Simulation, rate: f64) -> usize {
+    unsafe {
+        let mj_model = &mut *simulation.model.ptr();
+        let substeps = (rate * mj_model.opt.timestep).round().max(1.0) as usize;
+        mj_model.opt.timestep /= substeps as f64;
+        substeps
+    }
+}
@@ -0,0 +1,93 @@
+// main.rs
+mod ui;
+mod sensors;
+mod controller;
+mod mixer;
+mod session;
+mod mavlink;
+mod hil;
+mod synthetic;
+mod imu;
+mod propulsion;
+mod wind;
+mod ground_effect;
+mod battery;
+mod vehicle;
+mod mission;
+mod flight;
+mod swarm;
+
+use controller::StateSource;
+use std::path::PathBuf;
+
+// usage: mujoco-rust-FlightControl [--sensors] [--keyframe <name>] [--save-keyframes <file.xml>]
+//                                   [--hil <tcp:PORT|udp:PORT>] [--mock-autopilot] [--seed <n>]
+//                                   [--imu-rate <hz>] [--propulsion]
+//                                   [--wind <x,y,z>] [--turbulence <m/s>] [--gust <start,duration,x,y,z>]...
+//                                   [--ground-effect] [--battery <mAh>] [--scene <file.xml>] [--drones <n>]
+fn main() {
+    let mut options = flight::Options {
+        source: StateSource::GroundTruth,
+        start_keyframe: None,
+        save_path: None,
+        hil_endpoint: None,
+        mock_autopilot: false,
+        seed: 0,
+        imu_rate: None,
+        use_propulsion: false,
+        mean_wind: None,
+        turbulence: None,
+        gusts: Vec::new(),
+        use_ground_effect: false,
+        battery_capacity: None,
+        scene: String::from("../x2/scene.xml"),
+    };
+    let mut drones = 1;
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--sensors" => options.source = StateSource::Sensors,
+            "--keyframe" => options.start_keyframe = args.next(),
+            "--save-keyframes" => options.save_path = args.next().map(PathBuf::from),
+            "--hil" => options.hil_endpoint = args.next(),
+            "--mock-autopilot" => options.mock_autopilot = true,
+            "--propulsion" => options.use_propulsion = true,
+            "--ground-effect" => options.use_ground_effect = true,
+            "--battery" => options.battery_capacity = args.next().and_then(|s| s.parse().ok()),
+            "--scene" => options.scene = args.next().unwrap_or(options.scene),
+            "--drones" => drones = args.next().and_then(|s| s.parse().ok()).unwrap_or(1),
+            "--wind" => options.mean_wind = args.next().and_then(|s| parse_list(&s)).and_then(|v| v.try_into().ok()),
+            "--turbulence" => options.turbulence = args.next().and_then(|s| s.parse().ok()),
+            "--gust" => match args.next().and_then(|s| parse_list(&s)).as_deref() {
+                Some(&[start, duration, x, y, z]) => options.gusts.push(wind::Gust { start, duration, peak: [x, y, z] }),
+                _ => eprintln!("--gust expects start,duration,x,y,z"),
+            },
+            "--imu-rate" => options.imu_rate = args.next().and_then(|s| s.parse().ok()),
+            "--seed" => options.seed = args.next().and_then(|s| s.parse().ok()).unwrap_or(0),
+            _ => eprintln!("unknown argument: {}", arg),
+        }
+    }
+
+    println!("Sim Start...");
+    println!("--------------------------------------------------");
+
+    if drones > 1 {
+        // several drones in one model, each with its own controller
+        let ignored = options.single_drone_flags();
+        if !ignored.is_empty() {
+            eprintln!("{} only apply to a single drone, leave them out with --drones {}", ignored.join(", "), drones);
+            std::process::exit(2);
+        }
+        swarm::fly(&options.scene, drones, options.source, options.seed);
+    } else {
+        flight::fly(options);
+    }
+
+    println!("--------------------------------------------------");
+    println!("Sim Done.");
+}
+
+// "1,2,3" -> [1.0, 2.0, 3.0]
+fn parse_list(s: &str) -> Option<Vec<f64>> {
+    s.split(',').map(|v| v.trim().parse().ok()).collect()
+}
\ No newline at end of file
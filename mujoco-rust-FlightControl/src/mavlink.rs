@@ -0,0 +1,610 @@
+// mavlink.rs
+//
+// MAVLink v2 framing and the few common.xml messages needed for HIL.
+// Fields are in wire order: sorted by type size, extensions last.
+
+const MAGIC_V1: u8 = 0xFE;
+const MAGIC_V2: u8 = 0xFD;
+const HEADER_V1: usize = 6;
+const HEADER_V2: usize = 10;
+
+pub const MSG_HEARTBEAT: u32 = 0;
+pub const MSG_HIL_ACTUATOR_CONTROLS: u32 = 93;
+pub const MSG_HIL_SENSOR: u32 = 107;
+pub const MSG_HIL_GPS: u32 = 113;
+pub const MSG_HIL_STATE_QUATERNION: u32 = 115;
+pub const MSG_BATTERY_STATUS: u32 = 147;
+
+// (payload length with extensions, CRC_EXTRA)
+fn message_info(msgid: u32) -> Option<(usize, u8)> {
+    match msgid {
+        MSG_HEARTBEAT => Some((9, 50)),
+        MSG_HIL_ACTUATOR_CONTROLS => Some((81, 47)),
+        MSG_HIL_SENSOR => Some((65, 108)),
+        MSG_HIL_GPS => Some((39, 124)),
+        MSG_HIL_STATE_QUATERNION => Some((64, 4)),
+        MSG_BATTERY_STATUS => Some((54, 154)),
+        _ => None,
+    }
+}
+
+// MAV_MODE_FLAG_SAFETY_ARMED
+pub const MODE_FLAG_SAFETY_ARMED: u8 = 128;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct Heartbeat {
+    pub custom_mode: u32,
+    pub mav_type: u8,
+    pub autopilot: u8,
+    pub base_mode: u8,
+    pub system_status: u8,
+    pub mavlink_version: u8,
+}
+
+/// IMU, magnetometer & barometer readings, body frame FRD
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct HilSensor {
+    pub time_usec: u64,
+    pub acc: [f32; 3],    // m/s^2
+    pub gyro: [f32; 3],   // rad/s
+    pub mag: [f32; 3],    // gauss
+    pub abs_pressure: f32, // hPa
+    pub diff_pressure: f32,
+    pub pressure_alt: f32, // m
+    pub temperature: f32,  // degC
+    pub fields_updated: u32,
+    pub id: u8,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct HilGps {
+    pub time_usec: u64,
+    pub lat: i32, // degE7
+    pub lon: i32,
+    pub alt: i32, // mm above MSL
+    pub eph: u16, // HDOP * 100
+    pub epv: u16,
+    pub vel: u16, // ground speed cm/s
+    pub vn: i16,  // cm/s, NED
+    pub ve: i16,
+    pub vd: i16,
+    pub cog: u16, // cdeg, 0..35999
+    pub fix_type: u8,
+    pub satellites_visible: u8,
+    pub id: u8,
+    pub yaw: u16, // cdeg, 0 if unknown
+}
+
+/// Ground truth for the autopilot's estimator checks, NED & FRD
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct HilStateQuaternion {
+    pub time_usec: u64,
+    pub attitude_quaternion: [f32; 4], // w x y z
+    pub rollspeed: f32,
+    pub pitchspeed: f32,
+    pub yawspeed: f32,
+    pub lat: i32,
+    pub lon: i32,
+    pub alt: i32,
+    pub vx: i16, // cm/s
+    pub vy: i16,
+    pub vz: i16,
+    pub ind_airspeed: u16,
+    pub true_airspeed: u16,
+    pub xacc: i16, // mG
+    pub yacc: i16,
+    pub zacc: i16,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct HilActuatorControls {
+    pub time_usec: u64,
+    pub flags: u64,
+    pub controls: [f32; 16], // motors 0..1, others -1..1
+    pub mode: u8,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct BatteryStatus {
+    pub current_consumed: i32, // mAh
+    pub energy_consumed: i32,  // hJ
+    pub temperature: i16,      // cdegC, i16::MAX if unknown
+    pub voltages: [u16; 10],   // mV per cell, u16::MAX for no cell
+    pub current_battery: i16,  // cA
+    pub id: u8,
+    pub battery_function: u8,
+    pub battery_type: u8,      // MAV_BATTERY_TYPE, 1 is LiPo
+    pub battery_remaining: i8, // %
+    pub time_remaining: i32,   // s
+    pub charge_state: u8,      // MAV_BATTERY_CHARGE_STATE
+    pub voltages_ext: [u16; 4],
+    pub mode: u8,
+    pub fault_bitmask: u32,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Message {
+    Heartbeat(Heartbeat),
+    HilSensor(HilSensor),
+    HilGps(HilGps),
+    HilStateQuaternion(HilStateQuaternion),
+    HilActuatorControls(HilActuatorControls),
+    BatteryStatus(BatteryStatus),
+}
+
+impl Message {
+    pub fn msgid(&self) -> u32 {
+        match self {
+            Message::Heartbeat(_) => MSG_HEARTBEAT,
+            Message::HilSensor(_) => MSG_HIL_SENSOR,
+            Message::HilGps(_) => MSG_HIL_GPS,
+            Message::HilStateQuaternion(_) => MSG_HIL_STATE_QUATERNION,
+            Message::HilActuatorControls(_) => MSG_HIL_ACTUATOR_CONTROLS,
+            Message::BatteryStatus(_) => MSG_BATTERY_STATUS,
+        }
+    }
+
+    fn payload(&self) -> Vec<u8> {
+        let mut w = Writer::default();
+        match self {
+            Message::Heartbeat(m) => {
+                w.u32(m.custom_mode);
+                w.u8(m.mav_type);
+                w.u8(m.autopilot);
+                w.u8(m.base_mode);
+                w.u8(m.system_status);
+                w.u8(m.mavlink_version);
+            }
+            Message::HilSensor(m) => {
+                w.u64(m.time_usec);
+                m.acc.iter().chain(m.gyro.iter()).chain(m.mag.iter()).for_each(|v| w.f32(*v));
+                w.f32(m.abs_pressure);
+                w.f32(m.diff_pressure);
+                w.f32(m.pressure_alt);
+                w.f32(m.temperature);
+                w.u32(m.fields_updated);
+                w.u8(m.id);
+            }
+            Message::HilGps(m) => {
+                w.u64(m.time_usec);
+                w.i32(m.lat);
+                w.i32(m.lon);
+                w.i32(m.alt);
+                w.u16(m.eph);
+                w.u16(m.epv);
+                w.u16(m.vel);
+                w.i16(m.vn);
+                w.i16(m.ve);
+                w.i16(m.vd);
+                w.u16(m.cog);
+                w.u8(m.fix_type);
+                w.u8(m.satellites_visible);
+                w.u8(m.id);
+                w.u16(m.yaw);
+            }
+            Message::HilStateQuaternion(m) => {
+                w.u64(m.time_usec);
+                m.attitude_quaternion.iter().for_each(|v| w.f32(*v));
+                w.f32(m.rollspeed);
+                w.f32(m.pitchspeed);
+                w.f32(m.yawspeed);
+                w.i32(m.lat);
+                w.i32(m.lon);
+                w.i32(m.alt);
+                w.i16(m.vx);
+                w.i16(m.vy);
+                w.i16(m.vz);
+                w.u16(m.ind_airspeed);
+                w.u16(m.true_airspeed);
+                w.i16(m.xacc);
+                w.i16(m.yacc);
+                w.i16(m.zacc);
+            }
+            Message::HilActuatorControls(m) => {
+                w.u64(m.time_usec);
+                w.u64(m.flags);
+                m.controls.iter().for_each(|v| w.f32(*v));
+                w.u8(m.mode);
+            }
+            Message::BatteryStatus(m) => {
+                w.i32(m.current_consumed);
+                w.i32(m.energy_consumed);
+                w.i16(m.temperature);
+                m.voltages.iter().for_each(|v| w.u16(*v));
+                w.i16(m.current_battery);
+                w.u8(m.id);
+                w.u8(m.battery_function);
+                w.u8(m.battery_type);
+                w.i8(m.battery_remaining);
+                w.i32(m.time_remaining);
+                w.u8(m.charge_state);
+                m.voltages_ext.iter().for_each(|v| w.u16(*v));
+                w.u8(m.mode);
+                w.u32(m.fault_bitmask);
+            }
+        }
+        w.0
+    }
+
+    // payload is zero extended to the full length
+    fn decode(msgid: u32, payload: &[u8]) -> Option<Message> {
+        let mut r = Reader { data: payload, pos: 0 };
+        let message = match msgid {
+            MSG_HEARTBEAT => Message::Heartbeat(Heartbeat {
+                custom_mode: r.u32(),
+                mav_type: r.u8(),
+                autopilot: r.u8(),
+                base_mode: r.u8(),
+                system_status: r.u8(),
+                mavlink_version: r.u8(),
+            }),
+            MSG_HIL_SENSOR => Message::HilSensor(HilSensor {
+                time_usec: r.u64(),
+                acc: [r.f32(), r.f32(), r.f32()],
+                gyro: [r.f32(), r.f32(), r.f32()],
+                mag: [r.f32(), r.f32(), r.f32()],
+                abs_pressure: r.f32(),
+                diff_pressure: r.f32(),
+                pressure_alt: r.f32(),
+                temperature: r.f32(),
+                fields_updated: r.u32(),
+                id: r.u8(),
+            }),
+            MSG_HIL_GPS => Message::HilGps(HilGps {
+                time_usec: r.u64(),
+                lat: r.i32(),
+                lon: r.i32(),
+                alt: r.i32(),
+                eph: r.u16(),
+                epv: r.u16(),
+                vel: r.u16(),
+                vn: r.i16(),
+                ve: r.i16(),
+                vd: r.i16(),
+                cog: r.u16(),
+                fix_type: r.u8(),
+                satellites_visible: r.u8(),
+                id: r.u8(),
+                yaw: r.u16(),
+            }),
+            MSG_HIL_STATE_QUATERNION => Message::HilStateQuaternion(HilStateQuaternion {
+                time_usec: r.u64(),
+                attitude_quaternion: [r.f32(), r.f32(), r.f32(), r.f32()],
+                rollspeed: r.f32(),
+                pitchspeed: r.f32(),
+                yawspeed: r.f32(),
+                lat: r.i32(),
+                lon: r.i32(),
+                alt: r.i32(),
+                vx: r.i16(),
+                vy: r.i16(),
+                vz: r.i16(),
+                ind_airspeed: r.u16(),
+                true_airspeed: r.u16(),
+                xacc: r.i16(),
+                yacc: r.i16(),
+                zacc: r.i16(),
+            }),
+            MSG_HIL_ACTUATOR_CONTROLS => {
+                let time_usec = r.u64();
+                let flags = r.u64();
+                let mut controls = [0.0; 16];
+                controls.iter_mut().for_each(|c| *c = r.f32());
+                Message::HilActuatorControls(HilActuatorControls { time_usec, flags, controls, mode: r.u8() })
+            }
+            MSG_BATTERY_STATUS => Message::BatteryStatus(BatteryStatus {
+                current_consumed: r.i32(),
+                energy_consumed: r.i32(),
+                temperature: r.i16(),
+                voltages: std::array::from_fn(|_| r.u16()),
+                current_battery: r.i16(),
+                id: r.u8(),
+                battery_function: r.u8(),
+                battery_type: r.u8(),
+                battery_remaining: r.i8(),
+                time_remaining: r.i32(),
+                charge_state: r.u8(),
+                voltages_ext: std::array::from_fn(|_| r.u16()),
+                mode: r.u8(),
+                fault_bitmask: r.u32(),
+            }),
+            _ => return None,
+        };
+        Some(message)
+    }
+}
+
+/// A MAVLink v2 frame for the message, trailing zeros of the payload are
+/// truncated as the v2 spec asks
+pub fn encode(message: &Message, seq: u8, sysid: u8, compid: u8) -> Vec<u8> {
+    let msgid = message.msgid();
+    let mut payload = message.payload();
+    while payload.len() > 1 && payload.last() == Some(&0) {
+        payload.pop();
+    }
+
+    let mut frame = vec![MAGIC_V2, payload.len() as u8, 0, 0, seq, sysid, compid];
+    frame.extend_from_slice(&msgid.to_le_bytes()[..3]);
+    frame.extend_from_slice(&payload);
+    let crc_extra = message_info(msgid).map_or(0, |(_, extra)| extra);
+    let crc = crc_x25(&frame[1..], crc_extra);
+    frame.extend_from_slice(&crc.to_le_bytes());
+    frame
+}
+
+/// Splits a byte stream into messages, v1 & v2 frames. Unknown messages and
+/// frames with a bad checksum are dropped: only their start byte, as it may
+/// have been noise and a valid frame can start inside the claimed length.
+#[derive(Default)]
+pub struct Parser {
+    buffer: Vec<u8>,
+    pub dropped: usize,
+}
+
+impl Parser {
+    pub fn push(&mut self, data: &[u8]) -> Vec<(u8, Message)> {
+        self.buffer.extend_from_slice(data);
+        let mut messages = Vec::new();
+        loop {
+            // skip to the next start byte
+            match self.buffer.iter().position(|&b| b == MAGIC_V1 || b == MAGIC_V2) {
+                Some(start) => {
+                    self.buffer.drain(..start);
+                }
+                None => {
+                    self.buffer.clear();
+                    break;
+                }
+            }
+            if self.buffer.len() < 2 {
+                break;
+            }
+            let v2 = self.buffer[0] == MAGIC_V2;
+            let len = self.buffer[1] as usize;
+            let header = if v2 { HEADER_V2 } else { HEADER_V1 };
+            let signed = v2 && self.buffer.len() > 2 && self.buffer[2] & 0x01 != 0;
+            let total = header + len + 2 + if signed { 13 } else { 0 };
+            if self.buffer.len() < total {
+                break;
+            }
+
+            let frame = &self.buffer[..total];
+            let (sysid, msgid) = if v2 {
+                (frame[5], u32::from_le_bytes([frame[7], frame[8], frame[9], 0]))
+            } else {
+                (frame[3], frame[5] as u32)
+            };
+            let Some((full_len, crc_extra)) = message_info(msgid) else {
+                self.buffer.drain(..1);
+                continue;
+            };
+            let crc = u16::from_le_bytes([frame[header + len], frame[header + len + 1]]);
+            if crc != crc_x25(&frame[1..header + len], crc_extra) {
+                self.dropped += 1;
+                self.buffer.drain(..1);
+                continue;
+            }
+            let mut payload = frame[header..header + len].to_vec();
+            payload.resize(full_len.max(len), 0);
+            self.buffer.drain(..total);
+            if let Some(message) = Message::decode(msgid, &payload) {
+                messages.push((sysid, message));
+            }
+        }
+        messages
+    }
+}
+
+// CRC-16/MCRF4XX as used by MAVLink, CRC_EXTRA appended
+fn crc_x25(data: &[u8], crc_extra: u8) -> u16 {
+    let mut crc: u16 = 0xFFFF;
+    for &byte in data.iter().chain(std::iter::once(&crc_extra)) {
+        let mut tmp = byte ^ (crc & 0xFF) as u8;
+        tmp ^= tmp << 4;
+        crc = (crc >> 8) ^ ((tmp as u16) << 8) ^ ((tmp as u16) << 3) ^ ((tmp as u16) >> 4);
+    }
+    crc
+}
+
+#[derive(Default)]
+struct Writer(Vec<u8>);
+
+impl Writer {
+    fn u8(&mut self, v: u8) {
+        self.0.push(v);
+    }
+    fn i8(&mut self, v: i8) {
+        self.0.push(v as u8);
+    }
+    fn u16(&mut self, v: u16) {
+        self.0.extend_from_slice(&v.to_le_bytes());
+    }
+    fn i16(&mut self, v: i16) {
+        self.0.extend_from_slice(&v.to_le_bytes());
+    }
+    fn u32(&mut self, v: u32) {
+        self.0.extend_from_slice(&v.to_le_bytes());
+    }
+    fn i32(&mut self, v: i32) {
+        self.0.extend_from_slice(&v.to_le_bytes());
+    }
+    fn u64(&mut self, v: u64) {
+        self.0.extend_from_slice(&v.to_le_bytes());
+    }
+    fn f32(&mut self, v: f32) {
+        self.0.extend_from_slice(&v.to_le_bytes());
+    }
+}
+
+struct Reader<'a> {
+    data: &'a [u8],
+    pos: usize,
+}
+
+impl Reader<'_> {
+    fn take<const N: usize>(&mut self) -> [u8; N] {
+        let mut bytes = [0; N];
+        bytes.copy_from_slice(&self.data[self.pos..self.pos + N]);
+        self.pos += N;
+        bytes
+    }
+    fn u8(&mut self) -> u8 {
+        self.take::<1>()[0]
+    }
+    fn i8(&mut self) -> i8 {
+        self.take::<1>()[0] as i8
+    }
+    fn u16(&mut self) -> u16 {
+        u16::from_le_bytes(self.take())
+    }
+    fn i16(&mut self) -> i16 {
+        i16::from_le_bytes(self.take())
+    }
+    fn u32(&mut self) -> u32 {
+        u32::from_le_bytes(self.take())
+    }
+    fn i32(&mut self) -> i32 {
+        i32::from_le_bytes(self.take())
+    }
+    fn u64(&mut self) -> u64 {
+        u64::from_le_bytes(self.take())
+    }
+    fn f32(&mut self) -> f32 {
+        f32::from_le_bytes(self.take())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn messages() -> Vec<Message> {
+        vec![
+            Message::Heartbeat(Heartbeat { custom_mode: 65536, mav_type: 2, autopilot: 12, base_mode: MODE_FLAG_SAFETY_ARMED, system_status: 4, mavlink_version: 3 }),
+            Message::HilSensor(HilSensor {
+                time_usec: 1_234_567,
+                acc: [0.1, -0.2, -9.81],
+                gyro: [0.01, 0.02, -0.03],
+                mag: [0.21, 0.0, 0.43],
+                abs_pressure: 1013.25,
+                diff_pressure: 0.0,
+                pressure_alt: 488.0,
+                temperature: 15.0,
+                fields_updated: 0x1FFF,
+                id: 0,
+            }),
+            Message::HilGps(HilGps {
+                time_usec: 1_234_567,
+                lat: 473_977_420,
+                lon: 85_455_940,
+                alt: 488_000,
+                eph: 100,
+                epv: 150,
+                vel: 120,
+                vn: 100,
+                ve: -66,
+                vd: 5,
+                cog: 32_000,
+                fix_type: 3,
+                satellites_visible: 10,
+                id: 0,
+                yaw: 0,
+            }),
+            Message::HilStateQuaternion(HilStateQuaternion {
+                time_usec: 1_234_567,
+                attitude_quaternion: [0.9239, 0.0, 0.0, 0.3827],
+                rollspeed: 0.1,
+                pitchspeed: -0.1,
+                yawspeed: 0.5,
+                lat: 473_977_420,
+                lon: 85_455_940,
+                alt: 489_000,
+                vx: 100,
+                vy: -50,
+                vz: 0,
+                ind_airspeed: 112,
+                true_airspeed: 112,
+                xacc: 0,
+                yacc: 0,
+                zacc: -1000,
+            }),
+            Message::HilActuatorControls(HilActuatorControls {
+                time_usec: 1_234_567,
+                flags: 1,
+                controls: std::array::from_fn(|i| if i < 4 { 0.5 + 0.05 * i as f32 } else { 0.0 }),
+                mode: MODE_FLAG_SAFETY_ARMED,
+            }),
+            Message::BatteryStatus(BatteryStatus {
+                current_consumed: 850,
+                energy_consumed: -1,
+                temperature: i16::MAX,
+                voltages: std::array::from_fn(|i| if i < 4 { 3900 } else { u16::MAX }),
+                current_battery: 2150,
+                id: 0,
+                battery_function: 1,
+                battery_type: 1,
+                battery_remaining: 72,
+                time_remaining: 540,
+                charge_state: 1,
+                voltages_ext: [0; 4],
+                mode: 0,
+                fault_bitmask: 0,
+            }),
+        ]
+    }
+
+    #[test]
+    fn crc_check_value() {
+        // CRC-16/MCRF4XX of "123456789", the last byte in place of CRC_EXTRA
+        assert_eq!(crc_x25(b"12345678", b'9'), 0x6F91);
+    }
+
+    #[test]
+    fn encode_parse_round_trip() {
+        for (seq, message) in messages().into_iter().enumerate() {
+            let frame = encode(&message, seq as u8, 1, 1);
+            assert_eq!(frame[0], MAGIC_V2);
+            assert_eq!(frame.len(), HEADER_V2 + frame[1] as usize + 2);
+            let mut parser = Parser::default();
+            assert_eq!(parser.push(&frame), vec![(1, message)]);
+            assert_eq!(parser.dropped, 0);
+        }
+    }
+
+    #[test]
+    fn frames_split_across_pushes() {
+        let stream: Vec<u8> = messages().iter().flat_map(|m| encode(m, 0, 7, 1)).collect();
+        let mut parser = Parser::default();
+        let mut parsed = Vec::new();
+        for chunk in stream.chunks(5) {
+            parsed.extend(parser.push(chunk).into_iter().map(|(_, m)| m));
+        }
+        assert_eq!(parsed, messages());
+    }
+
+    #[test]
+    fn resync_after_a_false_start_byte() {
+        // a start byte in the noise claims a 255 byte payload, the valid
+        // frames behind it must not be swallowed with it
+        let mut stream = vec![0x00, MAGIC_V2, 0xFF, 0x00];
+        for _ in 0..4 {
+            stream.extend(messages().iter().flat_map(|m| encode(m, 0, 1, 1)));
+        }
+        let mut parser = Parser::default();
+        let parsed: Vec<Message> = parser.push(&stream).into_iter().map(|(_, m)| m).collect();
+        assert_eq!(parsed.len(), 4 * messages().len());
+        assert_eq!(&parsed[..messages().len()], &messages()[..]);
+    }
+
+    #[test]
+    fn corrupted_frame_is_dropped() {
+        let mut frame = encode(&messages()[0], 0, 1, 1);
+        let last = frame.len() - 1;
+        frame[last] ^= 0xFF;
+        frame.extend(encode(&messages()[1], 1, 1, 1));
+        let mut parser = Parser::default();
+        let parsed = parser.push(&frame);
+        assert_eq!(parsed, vec![(1, messages()[1])]);
+        assert_eq!(parser.dropped, 1);
+    }
+}
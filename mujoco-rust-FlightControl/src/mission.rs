@@ -0,0 +1,42 @@
+// mission.rs
+use crate::controller::{DroneState, Setpoint};
+
+/// One leg of the flight
+#[derive(Debug, Clone, Copy)]
+pub enum Phase {
+    Takeoff(f64),       // climb to this height
+    Waypoint([f64; 3]), // fly to a position
+    Land,               // descend, motors off on the ground
+}
+
+/// Take off, fly a square inside the walls of scene.xml, land in the center
+pub const MISSION: [Phase; 7] = [
+    Phase::Takeoff(1.0),
+    Phase::Waypoint([1.0, 0.0, 1.5]),
+    Phase::Waypoint([1.0, 1.0, 1.5]),
+    Phase::Waypoint([-1.0, 1.0, 1.5]),
+    Phase::Waypoint([-1.0, -1.0, 1.5]),
+    Phase::Waypoint([0.0, 0.0, 1.0]),
+    Phase::Land,
+];
+const WAYPOINT_RADIUS: f64 = 0.15;
+const LANDED_HEIGHT: f64 = 0.05; // body origin is ~0 when resting on the floor
+
+/// Whether the drone is through `phase`, never for `Land`
+pub fn phase_done(phase: Phase, state: &DroneState) -> bool {
+    match phase {
+        Phase::Takeoff(height) => state.pos[2] >= height,
+        Phase::Waypoint(target) => (0..3).map(|i| (target[i] - state.pos[i]).powi(2)).sum::<f64>().sqrt() < WAYPOINT_RADIUS,
+        Phase::Land => false,
+    }
+}
+
+/// What the controller is asked to do during `phase`
+pub fn phase_setpoint(phase: Phase, state: &DroneState) -> Setpoint {
+    match phase {
+        Phase::Takeoff(_) => Setpoint::Velocity { vel: [0.0, 0.0, 0.5], yaw: 0.0 },
+        Phase::Waypoint(pos) => Setpoint::Position { pos, yaw: 0.0 },
+        Phase::Land if state.pos[2] > LANDED_HEIGHT => Setpoint::Velocity { vel: [0.0, 0.0, -0.3], yaw: 0.0 },
+        Phase::Land => Setpoint::Attitude { roll: 0.0, pitch: 0.0, yaw: state.yaw(), thrust: 0.0 },
+    }
+}
@@ -0,0 +1,325 @@
+// mixer.rs
+use crate::controller::{cross, quat_to_mat, Command};
+use mujoco_rust::Simulation;
+use std::fmt;
+
+// mjTRN_SITE
+const TRN_SITE: i32 = 4;
+// mjNGAIN in MuJoCo 2.3.5
+const NGAIN: usize = 10;
+
+/// One actuator pushing on the airframe through a site
+#[derive(Debug, Clone)]
+pub struct Motor {
+    pub actuator: usize,     // index in ctrl
+    pub pos: [f64; 3],       // site position relative to the center of mass, body frame
+    pub force: [f64; 3],     // force per unit ctrl, body frame
+    pub torque: [f64; 3],    // torque about the center of mass per unit ctrl, body frame
+    pub ctrlrange: [f64; 2], // (-inf, inf) if not ctrllimited
+}
+
+/// Allocation from collective thrust & body torques to actuator ctrl
+///
+/// Built from the model: every actuator with a site transmission on the
+/// body or one of its children is a motor. Its column of the allocation
+/// matrix comes from `actuator_gear` (force & torque in the site frame),
+/// the site pose and the gain of the actuator.
+pub struct Mixer {
+    pub motors: Vec<Motor>,
+    pub weights: [f64; 4], // thrust, roll, pitch, yaw in the least squares fit
+    matrix: Vec<[f64; 4]>, // column per motor: thrust, roll, pitch, yaw
+}
+
+impl Mixer {
+    pub fn new(simulation: &Simulation, body: usize) -> Self {
+        let mut motors = Vec::new();
+        let com = subtree_com(simulation, body);
+        unsafe {
+            let mj_model = &*simulation.model.ptr();
+            for actuator in 0..mj_model.nu as usize {
+                if *mj_model.actuator_trntype.add(actuator) != TRN_SITE {
+                    continue;
+                }
+                let site = *mj_model.actuator_trnid.add(2 * actuator) as usize;
+                let site_pos = read3(mj_model.site_pos.add(3 * site));
+                let site_quat = read4(mj_model.site_quat.add(4 * site));
+                let site_body = *mj_model.site_bodyid.add(site) as usize;
+                let Some((pos, rot)) = pose_in_body(simulation, site_body, body, site_pos, quat_to_mat(&site_quat)) else {
+                    continue;
+                };
+
+                let gear = std::slice::from_raw_parts(mj_model.actuator_gear.add(6 * actuator), 6);
+                let gain = *mj_model.actuator_gainprm.add(NGAIN * actuator);
+                let force = mat_vec(&rot, &[gear[0] * gain, gear[1] * gain, gear[2] * gain]);
+                let moment = mat_vec(&rot, &[gear[3] * gain, gear[4] * gain, gear[5] * gain]);
+                let pos = [pos[0] - com[0], pos[1] - com[1], pos[2] - com[2]];
+                let arm = cross(&pos, &force);
+
+                let ctrlrange = if *mj_model.actuator_ctrllimited.add(actuator) != 0 {
+                    [*mj_model.actuator_ctrlrange.add(2 * actuator), *mj_model.actuator_ctrlrange.add(2 * actuator + 1)]
+                } else {
+                    [f64::NEG_INFINITY, f64::INFINITY]
+                };
+                motors.push(Motor {
+                    actuator,
+                    pos,
+                    force,
+                    torque: [arm[0] + moment[0], arm[1] + moment[1], arm[2] + moment[2]],
+                    ctrlrange,
+                });
+            }
+        }
+
+        // only the force along body z is used, tilted rotors lose the rest
+        let matrix = motors.iter().map(|m| [m.force[2], m.torque[0], m.torque[1], m.torque[2]]).collect();
+        Mixer { motors, weights: [1.0, 1.0, 1.0, 0.01], matrix }
+    }
+
+    /// Number of independent axes among thrust, roll, pitch & yaw, less than
+    /// 4 means some of them cannot be commanded on their own
+    pub fn rank(&self) -> usize {
+        let mut rows: Vec<Vec<f64>> = (0..4).map(|axis| self.matrix.iter().map(|c| c[axis]).collect()).collect();
+        let n = self.motors.len();
+        let mut rank = 0;
+        for col in 0..n {
+            let Some(pivot) = (rank..4).filter(|&r| rows[r][col].abs() > 1e-9).max_by(|&a, &b| rows[a][col].abs().total_cmp(&rows[b][col].abs())) else {
+                continue;
+            };
+            rows.swap(rank, pivot);
+            for r in 0..4 {
+                if r != rank {
+                    let factor = rows[r][col] / rows[rank][col];
+                    for k in col..n {
+                        rows[r][k] -= factor * rows[rank][k];
+                    }
+                }
+            }
+            rank += 1;
+            if rank == 4 {
+                break;
+            }
+        }
+        rank
+    }
+
+    /// Write the ctrl of every motor for a command, returns true when the
+    /// command had to be reduced to stay inside ctrlrange
+    ///
+    /// Roll & pitch come first, then yaw, then collective thrust: the thrust
+    /// is shifted to fit the torques in the range, yaw and then roll/pitch
+    /// torques are scaled down only when no thrust fits.
+    pub fn mix(&self, command: &Command, ctrl: &mut [f64]) -> bool {
+        let [tx, ty, tz] = command.torque;
+        let per_thrust = self.solve(&[1.0, 0.0, 0.0, 0.0]);
+        let roll_pitch = self.solve(&[0.0, tx, ty, 0.0]);
+        let yaw = self.solve(&[0.0, 0.0, 0.0, tz]);
+
+        let combine = |rp_scale: f64, yaw_scale: f64| -> Vec<f64> {
+            roll_pitch.iter().zip(yaw.iter()).map(|(rp, y)| rp * rp_scale + y * yaw_scale).collect()
+        };
+        let full = combine(1.0, 1.0);
+        let (torques, thrust, mut saturated) = match self.thrust_interval(&full, &per_thrust) {
+            Some((lo, hi)) => {
+                let thrust = command.thrust.clamp(lo, hi);
+                (full, thrust, thrust != command.thrust)
+            }
+            None => {
+                // drop yaw, then roll & pitch, as little as possible
+                let (rp_scale, yaw_scale) = if self.thrust_interval(&combine(1.0, 0.0), &per_thrust).is_some() {
+                    (1.0, self.max_scale(|s| combine(1.0, s), &per_thrust))
+                } else {
+                    (self.max_scale(|s| combine(s, 0.0), &per_thrust), 0.0)
+                };
+                let torques = combine(rp_scale, yaw_scale);
+                let thrust = match self.thrust_interval(&torques, &per_thrust) {
+                    Some((lo, hi)) => command.thrust.clamp(lo, hi),
+                    None => command.thrust,
+                };
+                (torques, thrust, true)
+            }
+        };
+
+        for (i, motor) in self.motors.iter().enumerate() {
+            let value = torques[i] + thrust * per_thrust[i];
+            let clamped = value.clamp(motor.ctrlrange[0], motor.ctrlrange[1]);
+            saturated |= (clamped - value).abs() > 1e-9;
+            ctrl[motor.actuator] = clamped;
+        }
+        saturated
+    }
+
+    // range of collective thrust that keeps every motor inside ctrlrange
+    fn thrust_interval(&self, torques: &[f64], per_thrust: &[f64]) -> Option<(f64, f64)> {
+        let mut lo = f64::NEG_INFINITY;
+        let mut hi = f64::INFINITY;
+        for (i, motor) in self.motors.iter().enumerate() {
+            let [min, max] = motor.ctrlrange;
+            if per_thrust[i].abs() < 1e-12 {
+                if torques[i] < min || torques[i] > max {
+                    return None;
+                }
+                continue;
+            }
+            let a = (min - torques[i]) / per_thrust[i];
+            let b = (max - torques[i]) / per_thrust[i];
+            lo = lo.max(a.min(b));
+            hi = hi.min(a.max(b));
+        }
+        (lo <= hi).then_some((lo, hi))
+    }
+
+    // largest scale in [0, 1] of the torques that still fits, by bisection
+    fn max_scale(&self, torques: impl Fn(f64) -> Vec<f64>, per_thrust: &[f64]) -> f64 {
+        if self.thrust_interval(&torques(0.0), per_thrust).is_none() {
+            return 0.0;
+        }
+        let (mut lo, mut hi) = (0.0, 1.0);
+        for _ in 0..20 {
+            let mid = 0.5 * (lo + hi);
+            if self.thrust_interval(&torques(mid), per_thrust).is_some() {
+                lo = mid;
+            } else {
+                hi = mid;
+            }
+        }
+        lo
+    }
+
+    // weighted least squares, (B^T W B + damping) u = B^T W v, the damping
+    // keeps it solvable when the matrix is rank deficient
+    fn solve(&self, wanted: &[f64; 4]) -> Vec<f64> {
+        let n = self.motors.len();
+        let mut m = vec![vec![0.0; n]; n];
+        let mut rhs = vec![0.0; n];
+        for i in 0..n {
+            for j in 0..n {
+                m[i][j] = (0..4).map(|k| self.matrix[i][k] * self.weights[k] * self.matrix[j][k]).sum();
+            }
+            m[i][i] += 1e-6;
+            rhs[i] = (0..4).map(|k| self.matrix[i][k] * self.weights[k] * wanted[k]).sum();
+        }
+        solve(m, rhs)
+    }
+}
+
+impl fmt::Display for Mixer {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(
+            f,
+            "{:>8} {:>22} {:>9} {:>9} {:>9} {:>9} {:>14}",
+            "actuator", "pos", "thrust", "roll", "pitch", "yaw", "ctrlrange"
+        )?;
+        for (motor, column) in self.motors.iter().zip(self.matrix.iter()) {
+            writeln!(
+                f,
+                "{:>8} [{:>6.3}, {:>6.3}, {:>6.3}] {:>9.4} {:>9.4} {:>9.4} {:>9.4} {:>6.2}..{:<6.2}",
+                motor.actuator,
+                motor.pos[0],
+                motor.pos[1],
+                motor.pos[2],
+                column[0],
+                column[1],
+                column[2],
+                column[3],
+                motor.ctrlrange[0],
+                motor.ctrlrange[1]
+            )?;
+        }
+        write!(f, "rank {} of 4", self.rank())
+    }
+}
+
+// pose of a point & frame given in `from` body expressed in `to` body,
+// None if `from` is not `to` or one of its children
+fn pose_in_body(simulation: &Simulation, from: usize, to: usize, mut pos: [f64; 3], mut rot: [f64; 9]) -> Option<([f64; 3], [f64; 9])> {
+    let mut body = from;
+    unsafe {
+        let mj_model = &*simulation.model.ptr();
+        while body != to {
+            if body == 0 {
+                return None;
+            }
+            let parent_rot = quat_to_mat(&read4(mj_model.body_quat.add(4 * body)));
+            let offset = read3(mj_model.body_pos.add(3 * body));
+            let p = mat_vec(&parent_rot, &pos);
+            pos = [p[0] + offset[0], p[1] + offset[1], p[2] + offset[2]];
+            rot = mat_mul(&parent_rot, &rot);
+            body = *mj_model.body_parentid.add(body) as usize;
+        }
+    }
+    Some((pos, rot))
+}
+
+// center of mass of the body & its children, in the body frame
+fn subtree_com(simulation: &Simulation, body: usize) -> [f64; 3] {
+    let mut com = [0.0; 3];
+    let mut mass = 0.0;
+    unsafe {
+        let mj_model = &*simulation.model.ptr();
+        for b in 0..mj_model.nbody as usize {
+            let ipos = read3(mj_model.body_ipos.add(3 * b));
+            if let Some((pos, _)) = pose_in_body(simulation, b, body, ipos, IDENTITY) {
+                let m = *mj_model.body_mass.add(b);
+                for i in 0..3 {
+                    com[i] += m * pos[i];
+                }
+                mass += m;
+            }
+        }
+    }
+    if mass > 0.0 {
+        com.iter_mut().for_each(|c| *c /= mass);
+    }
+    com
+}
+
+const IDENTITY: [f64; 9] = [1.0, 0.0, 0.0, 0.0, 1.0, 0.0, 0.0, 0.0, 1.0];
+
+unsafe fn read3(p: *const f64) -> [f64; 3] {
+    unsafe { [*p, *p.add(1), *p.add(2)] }
+}
+
+unsafe fn read4(p: *const f64) -> [f64; 4] {
+    unsafe { [*p, *p.add(1), *p.add(2), *p.add(3)] }
+}
+
+fn mat_vec(m: &[f64; 9], v: &[f64; 3]) -> [f64; 3] {
+    [
+        m[0] * v[0] + m[1] * v[1] + m[2] * v[2],
+        m[3] * v[0] + m[4] * v[1] + m[5] * v[2],
+        m[6] * v[0] + m[7] * v[1] + m[8] * v[2],
+    ]
+}
+
+fn mat_mul(a: &[f64; 9], b: &[f64; 9]) -> [f64; 9] {
+    let mut m = [0.0; 9];
+    for r in 0..3 {
+        for c in 0..3 {
+            m[3 * r + c] = (0..3).map(|k| a[3 * r + k] * b[3 * k + c]).sum();
+        }
+    }
+    m
+}
+
+// gaussian elimination with partial pivoting
+fn solve(mut m: Vec<Vec<f64>>, mut rhs: Vec<f64>) -> Vec<f64> {
+    let n = rhs.len();
+    for col in 0..n {
+        let pivot = (col..n).max_by(|&a, &b| m[a][col].abs().total_cmp(&m[b][col].abs())).unwrap();
+        m.swap(col, pivot);
+        rhs.swap(col, pivot);
+        for row in col + 1..n {
+            let factor = m[row][col] / m[col][col];
+            for k in col..n {
+                m[row][k] -= factor * m[col][k];
+            }
+            rhs[row] -= factor * rhs[col];
+        }
+    }
+    let mut x = vec![0.0; n];
+    for row in (0..n).rev() {
+        let sum: f64 = (row + 1..n).map(|k| m[row][k] * x[k]).sum();
+        x[row] = (rhs[row] - sum) / m[row][row];
+    }
+    x
+}
@@ -0,0 +1,162 @@
+// propulsion.rs
+use crate::controller::cross;
+use crate::mixer::Mixer;
+use mujoco_rust::Simulation;
+use std::f64::consts::PI;
+
+const RPM_TO_RAD_S: f64 = 2.0 * PI / 60.0;
+
+/// What a motor is told to spin at
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum RotorCommand {
+    Rpm(f64),
+    Pwm(f64), // µs, 1000 is stopped and 2000 full speed, linear in speed
+}
+
+/// Propeller on a motor with a first-order spin-up
+///
+/// Thrust `kt·ω²` along the force direction of the actuator gear and drag
+/// torque `kq·ω²` about it, with the sign of the torque in the gear.
+#[derive(Debug, Clone)]
+pub struct Rotor {
+    pub actuator: usize, // the motor actuator it stands in for
+    pub kt: f64,         // N/(rad/s)²
+    pub kq: f64,         // Nm/(rad/s)²
+    pub tau: f64,        // spin-up time constant (s)
+    pub max_rpm: f64,
+    pub command: RotorCommand,
+    pub thrust_scale: f64, // e.g. ground effect, 1 in free air
+    site: usize,
+    force_dir: [f64; 3],  // unit thrust direction, site frame
+    torque_dir: [f64; 3], // unit drag torque direction, site frame
+    omega: f64,           // rad/s
+}
+
+impl Rotor {
+    /// Commanded speed (rad/s)
+    fn target(&self) -> f64 {
+        let rpm = match self.command {
+            RotorCommand::Rpm(rpm) => rpm,
+            RotorCommand::Pwm(us) => (us - 1000.0) / 1000.0 * self.max_rpm,
+        };
+        rpm.clamp(0.0, self.max_rpm) * RPM_TO_RAD_S
+    }
+
+    pub fn rpm(&self) -> f64 {
+        self.omega / RPM_TO_RAD_S
+    }
+
+    pub fn thrust(&self) -> f64 {
+        self.thrust_scale * self.kt * self.omega * self.omega
+    }
+
+    /// Speed that gives a thrust once spun up
+    pub fn rpm_for_thrust(&self, thrust: f64) -> f64 {
+        (thrust.max(0.0) / self.kt).sqrt() / RPM_TO_RAD_S
+    }
+}
+
+/// Rotors in place of the ideal `<motor>` actuators of the mixer, where
+/// ctrl is thrust with no lag. Forces & torques go into `xfrc_applied`; the
+/// ctrl of the motor actuators has to stay 0.
+pub struct Propulsion {
+    pub rotors: Vec<Rotor>,
+}
+
+impl Propulsion {
+    /// One rotor per motor of the mixer, sized so `max_rpm` gives the top of
+    /// ctrlrange and with the torque/thrust ratio of the gear
+    pub fn new(simulation: &Simulation, mixer: &Mixer, max_rpm: f64, tau: f64) -> Self {
+        let omega_max = max_rpm * RPM_TO_RAD_S;
+        let rotors = mixer
+            .motors
+            .iter()
+            .map(|motor| unsafe {
+                let mj_model = &*simulation.model.ptr();
+                let site = *mj_model.actuator_trnid.add(2 * motor.actuator) as usize;
+                let gear = std::slice::from_raw_parts(mj_model.actuator_gear.add(6 * motor.actuator), 6);
+                let f = (gear[0] * gear[0] + gear[1] * gear[1] + gear[2] * gear[2]).sqrt();
+                let torque = [gear[3] / f, gear[4] / f, gear[5] / f];
+                let ratio = (torque[0] * torque[0] + torque[1] * torque[1] + torque[2] * torque[2]).sqrt();
+                let kt = motor.ctrlrange[1] / (omega_max * omega_max);
+                Rotor {
+                    actuator: motor.actuator,
+                    kt,
+                    kq: kt * ratio,
+                    tau,
+                    max_rpm,
+                    command: RotorCommand::Rpm(0.0),
+                    thrust_scale: 1.0,
+                    site,
+                    force_dir: [gear[0] / f, gear[1] / f, gear[2] / f],
+                    torque_dir: if ratio > 0.0 { torque.map(|t| t / ratio) } else { [0.0; 3] },
+                    omega: 0.0,
+                }
+            })
+            .collect();
+        Propulsion { rotors }
+    }
+
+    /// Command every rotor to the speed for the thrust in its motor's ctrl,
+    /// as the mixer wrote it, and zero that ctrl
+    pub fn take_thrust(&mut self, ctrl: &mut [f64]) {
+        for rotor in self.rotors.iter_mut() {
+            rotor.command = RotorCommand::Rpm(rotor.rpm_for_thrust(ctrl[rotor.actuator]));
+            ctrl[rotor.actuator] = 0.0;
+        }
+    }
+
+    /// Stop all rotors at once, e.g. after a reset
+    pub fn reset(&mut self) {
+        for rotor in self.rotors.iter_mut() {
+            rotor.omega = 0.0;
+            rotor.command = RotorCommand::Rpm(0.0);
+        }
+    }
+
+    /// Spin the motors up or down over `dt` and add their forces to
+    /// `xfrc_applied`, call before every `simulation.step()` after
+    /// `clear_applied`
+    pub fn apply(&mut self, simulation: &Simulation, dt: f64) {
+        unsafe {
+            let mj_model = &*simulation.model.ptr();
+            let mj_data = &mut *simulation.state.ptr();
+            for rotor in self.rotors.iter_mut() {
+                let target = rotor.target();
+                rotor.omega += (target - rotor.omega) * (1.0 - (-dt / rotor.tau.max(1e-6)).exp());
+                let thrust = rotor.thrust();
+                let drag = rotor.kq * rotor.omega * rotor.omega;
+
+                let body = *mj_model.site_bodyid.add(rotor.site) as usize;
+                let xmat = std::slice::from_raw_parts(mj_data.site_xmat.add(9 * rotor.site), 9);
+                let site_pos = std::slice::from_raw_parts(mj_data.site_xpos.add(3 * rotor.site), 3);
+                let com = std::slice::from_raw_parts(mj_data.xipos.add(3 * body), 3);
+                let mut force = [0.0; 3];
+                let mut torque = [0.0; 3];
+                for i in 0..3 {
+                    for j in 0..3 {
+                        force[i] += xmat[3 * i + j] * rotor.force_dir[j] * thrust;
+                        torque[i] += xmat[3 * i + j] * rotor.torque_dir[j] * drag;
+                    }
+                }
+                // xfrc_applied acts at the center of mass of the body
+                let arm = [site_pos[0] - com[0], site_pos[1] - com[1], site_pos[2] - com[2]];
+                let moment = cross(&arm, &force);
+                let xfrc = std::slice::from_raw_parts_mut(mj_data.xfrc_applied.add(6 * body), 6);
+                for i in 0..3 {
+                    xfrc[i] += force[i];
+                    xfrc[3 + i] += torque[i] + moment[i];
+                }
+            }
+        }
+    }
+}
+
+/// Zero `xfrc_applied` of all bodies, the Rust-side forces are added on top
+/// of it every step
+pub fn clear_applied(simulation: &Simulation) {
+    unsafe {
+        let nbody = (*simulation.model.ptr()).nbody as usize;
+        std::slice::from_raw_parts_mut((*simulation.state.ptr()).xfrc_applied, 6 * nbody).fill(0.0);
+    }
+}
@@ -0,0 +1,108 @@
+// sensors.rs
+use crate::synthetic::Synthetic;
+use mujoco_compose::rng::Rng;
+use mujoco_rust::Simulation;
+use std::collections::BTreeMap;
+use std::ffi::CStr;
+
+/// Where the values of one sensor live in mj_data.sensordata
+#[derive(Debug, Clone, Copy)]
+pub struct SensorInfo {
+    pub adr: usize,
+    pub dim: usize,
+}
+
+// a sensor of the registry that MuJoCo does not simulate
+struct SyntheticSensor {
+    body: usize,
+    sensor: Synthetic,
+    values: Vec<f64>,
+}
+
+/// All named sensors of the model, so they are read by name instead of by
+/// their position in the XML, plus synthetic sensors added on the Rust side
+pub struct SensorRegistry {
+    sensors: BTreeMap<String, SensorInfo>,
+    synthetic: BTreeMap<String, SyntheticSensor>,
+    rng: Rng,
+}
+
+impl SensorRegistry {
+    /// The sensors of one drone in a model with several, e.g. `d2_body_gyro`
+    /// for the prefix `d2_`; they are registered without the prefix, so they
+    /// are read as `body_gyro` like in a single drone model. The prefix is
+    /// empty for `x2.xml` alone.
+    pub fn with_prefix(simulation: &Simulation, prefix: &str) -> Self {
+        let mut sensors = BTreeMap::new();
+        unsafe {
+            let mj_model = &*simulation.model.ptr();
+            for id in 0..mj_model.nsensor as usize {
+                let adr = *mj_model.name_sensoradr.add(id) as usize;
+                let name = CStr::from_ptr(mj_model.names.add(adr)).to_string_lossy();
+                let Some(name) = name.strip_prefix(prefix).filter(|n| !n.is_empty()) else {
+                    continue;
+                };
+                let info = SensorInfo {
+                    adr: *mj_model.sensor_adr.add(id) as usize,
+                    dim: *mj_model.sensor_dim.add(id) as usize,
+                };
+                sensors.insert(name.to_string(), info);
+            }
+        }
+        SensorRegistry { sensors, synthetic: BTreeMap::new(), rng: Rng::new(0) }
+    }
+
+    /// Add a synthetic sensor on a body, read by name like the others and
+    /// updated by `update`. A model sensor of the same name takes precedence.
+    pub fn add_synthetic(&mut self, name: &str, body: usize, sensor: Synthetic) {
+        let values = vec![0.0; sensor.dim()];
+        self.synthetic.insert(name.to_string(), SyntheticSensor { body, sensor, values });
+    }
+
+    /// Noise seed of the synthetic sensors, the same seed gives the same readings
+    pub fn seed(&mut self, seed: u64) {
+        self.rng = Rng::new(seed);
+    }
+
+    /// Compute the synthetic sensors, call after each `simulation.step()`
+    pub fn update(&mut self, simulation: &Simulation) {
+        for s in self.synthetic.values_mut() {
+            s.sensor.update(simulation, s.body, &mut self.rng, &mut s.values);
+        }
+    }
+
+    /// Clear the state of the synthetic sensors (delays, drift, ...)
+    pub fn reset(&mut self) {
+        for s in self.synthetic.values_mut() {
+            s.sensor.reset();
+            s.values.fill(0.0);
+        }
+    }
+
+    pub fn get(&self, name: &str) -> Option<SensorInfo> {
+        self.sensors.get(name).copied()
+    }
+
+    /// Current values of a sensor, None if the model has no sensor of that name
+    pub fn read<'a>(&'a self, simulation: &'a Simulation, name: &str) -> Option<&'a [f64]> {
+        let Some(info) = self.get(name) else {
+            return self.synthetic.get(name).map(|s| s.values.as_slice());
+        };
+        let data = simulation.sensordata();
+        Some(&data[info.adr..info.adr + info.dim])
+    }
+
+    /// Same as `read` for 3-axis sensors, e.g. `body_gyro`
+    pub fn read3(&self, simulation: &Simulation, name: &str) -> Option<[f64; 3]> {
+        self.read(simulation, name).map(|v| [v[0], v[1], v[2]])
+    }
+
+    /// Same as `read` for quaternions, e.g. `body_quat`
+    pub fn read4(&self, simulation: &Simulation, name: &str) -> Option<[f64; 4]> {
+        self.read(simulation, name).map(|v| [v[0], v[1], v[2], v[3]])
+    }
+
+    pub fn names(&self) -> impl Iterator<Item = &str> {
+        self.sensors.keys().chain(self.synthetic.keys()).map(|k| k.as_str())
+    }
+}
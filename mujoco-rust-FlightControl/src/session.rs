@@ -0,0 +1,188 @@
+// session.rs
+use mujoco_compose::mjcf::{self, Element};
+use mujoco_rs_sys::no_render;
+use mujoco_rust::Simulation;
+use std::error::Error;
+use std::ffi::CStr;
+use std::fs;
+use std::io::ErrorKind;
+use std::path::{Path, PathBuf};
+
+/// A state of the simulation that can be reset to, like `<key>` in MJCF
+#[derive(Debug, Clone)]
+pub struct Keyframe {
+    pub name: String,
+    pub time: f64,
+    pub qpos: Vec<f64>,
+    pub qvel: Vec<f64>,
+    pub act: Vec<f64>,
+    pub ctrl: Vec<f64>,
+    pub mocap_pos: Vec<f64>,
+    pub mocap_quat: Vec<f64>,
+}
+
+impl Keyframe {
+    /// `<key .../>` element with all the attributes
+    pub fn to_element(&self) -> Element {
+        let mut key = Element::new("key");
+        key.set_attr("name", &self.name);
+        key.set_attr("time", &self.time.to_string());
+        for (attribute, values) in [
+            ("qpos", &self.qpos),
+            ("qvel", &self.qvel),
+            ("act", &self.act),
+            ("ctrl", &self.ctrl),
+            ("mpos", &self.mocap_pos),
+            ("mquat", &self.mocap_quat),
+        ] {
+            if !values.is_empty() {
+                let values: Vec<String> = values.iter().map(|v| v.to_string()).collect();
+                key.set_attr(attribute, &values.join(" "));
+            }
+        }
+        key
+    }
+
+    /// The element on one line, e.g. to print
+    pub fn to_xml(&self) -> String {
+        self.to_element().to_string().trim_end().to_string()
+    }
+}
+
+/// The simulation of one model file, with the keyframes of the model plus
+/// the ones captured while running
+pub struct SimSession {
+    pub simulation: Simulation,
+    pub path: PathBuf,
+    captured: Vec<Keyframe>,
+}
+
+impl SimSession {
+    pub fn load(path: &str) -> Self {
+        let model = mujoco_rust::Model::from_xml(path.to_string()).unwrap();
+        let simulation = mujoco_rust::Simulation::new(model);
+        SimSession { simulation, path: PathBuf::from(path), captured: Vec::new() }
+    }
+
+    /// Names of the keyframes defined in the model, e.g. `hover`
+    pub fn model_keyframes(&self) -> Vec<String> {
+        unsafe {
+            let mj_model = &*self.simulation.model.ptr();
+            (0..mj_model.nkey as usize)
+                .map(|i| {
+                    let adr = *mj_model.name_keyadr.add(i) as usize;
+                    CStr::from_ptr(mj_model.names.add(adr)).to_string_lossy().into_owned()
+                })
+                .collect()
+        }
+    }
+
+    /// Model keyframes followed by the captured ones
+    pub fn keyframes(&self) -> Vec<String> {
+        let mut names = self.model_keyframes();
+        names.extend(self.captured.iter().map(|k| k.name.clone()));
+        names
+    }
+
+    /// Reset the simulation to a keyframe, false if there is none of that name
+    pub fn reset_to_keyframe(&self, name: &str) -> bool {
+        let m = self.simulation.model.ptr();
+        let d = self.simulation.state.ptr();
+        if let Some(key) = self.model_keyframes().iter().position(|k| k == name) {
+            unsafe {
+                no_render::mj_resetDataKeyframe(m, d, key as i32);
+                no_render::mj_forward(m, d);
+            }
+            return true;
+        }
+
+        // captured keyframes are not in mjModel, copy them in by hand
+        let Some(key) = self.captured.iter().rev().find(|k| k.name == name) else {
+            return false;
+        };
+        unsafe {
+            no_render::mj_resetData(m, d);
+            let mj_data = &mut *d;
+            mj_data.time = key.time;
+            copy(&key.qpos, mj_data.qpos);
+            copy(&key.qvel, mj_data.qvel);
+            copy(&key.act, mj_data.act);
+            copy(&key.ctrl, mj_data.ctrl);
+            copy(&key.mocap_pos, mj_data.mocap_pos);
+            copy(&key.mocap_quat, mj_data.mocap_quat);
+            no_render::mj_forward(m, d);
+        }
+        true
+    }
+
+    /// Store the current state as a keyframe, a previous capture of the same
+    /// name is replaced
+    pub fn capture_keyframe(&mut self, name: &str) -> &Keyframe {
+        let key = unsafe {
+            let mj_model = &*self.simulation.model.ptr();
+            let mj_data = &*self.simulation.state.ptr();
+            let nmocap = mj_model.nmocap as usize;
+            Keyframe {
+                name: name.to_string(),
+                time: mj_data.time,
+                qpos: read(mj_data.qpos, mj_model.nq as usize),
+                qvel: read(mj_data.qvel, mj_model.nv as usize),
+                act: read(mj_data.act, mj_model.na as usize),
+                ctrl: read(mj_data.ctrl, mj_model.nu as usize),
+                mocap_pos: read(mj_data.mocap_pos, 3 * nmocap),
+                mocap_quat: read(mj_data.mocap_quat, 4 * nmocap),
+            }
+        };
+        self.captured.retain(|k| k.name != name);
+        self.captured.push(key);
+        self.captured.last().unwrap()
+    }
+
+    /// Write the captured keyframes into an MJCF file, e.g. `../x2/x2.xml`
+    ///
+    /// Keys go into the existing `<keyframe>` section, or a new one at the
+    /// end. A file that does not exist yet is created as `<mujoco>` with only
+    /// the `<keyframe>` section, to `<include>` in a model. A `<key>` of the
+    /// same name written before is replaced. The file is written back through
+    /// `mjcf`, so its comments are dropped. Keyframes of the model should not
+    /// be captured under the same name, MuJoCo rejects duplicate key names
+    /// when loading.
+    pub fn write_keyframes(&self, path: &Path) -> Result<(), Box<dyn Error>> {
+        let mut root = match fs::read_to_string(path) {
+            Ok(xml) => mjcf::parse(&xml).map_err(|e| format!("{}: {}", path.display(), e))?,
+            Err(e) if e.kind() == ErrorKind::NotFound => Element::new("mujoco"),
+            Err(e) => return Err(e.into()),
+        };
+        if root.name != "mujoco" {
+            return Err(format!("{} is not an MJCF file", path.display()).into());
+        }
+        let section = match root.children.iter().position(|e| e.name == "keyframe") {
+            Some(i) => i,
+            None => {
+                root.children.push(Element::new("keyframe"));
+                root.children.len() - 1
+            }
+        };
+        let keyframe = &mut root.children[section];
+        for key in self.captured.iter() {
+            keyframe.children.retain(|e| !(e.name == "key" && e.attr("name") == Some(key.name.as_str())));
+            keyframe.children.push(key.to_element());
+        }
+        fs::write(path, root.to_string())?;
+        Ok(())
+    }
+}
+
+// arrays of size 0 (e.g. act without activations) may be null
+unsafe fn read(p: *const f64, n: usize) -> Vec<f64> {
+    if n == 0 {
+        return Vec::new();
+    }
+    unsafe { std::slice::from_raw_parts(p, n).to_vec() }
+}
+
+unsafe fn copy(values: &[f64], p: *mut f64) {
+    if !values.is_empty() {
+        unsafe { std::ptr::copy_nonoverlapping(values.as_ptr(), p, values.len()) }
+    }
+}
@@ -0,0 +1,110 @@
+// swarm.rs
+use crate::controller::StateSource;
+use crate::mission::{phase_done, phase_setpoint, Phase, MISSION};
+use crate::{session, ui, vehicle};
+use mujoco_compose::compose;
+
+const SPACING: f64 = 0.8; // between neighbours on the start ring
+const SCALE: f64 = 0.6; // of the square, so the formation stays inside the walls
+
+/// `drones` x2s composed into the scene on a ring around the center, flying
+/// a smaller MISSION in formation, each shifted by its place on the ring
+pub fn fly(scene: &str, drones: usize, source: StateSource, seed: u64) {
+    let radius = (SPACING * drones as f64 / (2.0 * std::f64::consts::PI)).max(SPACING / 2.0);
+    let mut composer = compose::Composer::new(scene);
+    let mut missions = Vec::new();
+    for i in 0..drones {
+        let angle = 2.0 * std::f64::consts::PI * i as f64 / drones as f64;
+        let offset = [radius * angle.cos(), radius * angle.sin()];
+        // the x2.xml the scene includes
+        let mut drone = compose::Drone::new(std::path::Path::new(scene).with_file_name("x2.xml"));
+        drone.prefix = format!("d{}_", i + 1);
+        drone.pos = Some([offset[0], offset[1], 0.1]);
+        composer.drones.push(drone);
+        missions.push(MISSION.map(|phase| match phase {
+            Phase::Waypoint([x, y, z]) => Phase::Waypoint([offset[0] + SCALE * x, offset[1] + SCALE * y, z]),
+            phase => phase,
+        }));
+    }
+    let path = match composer.write_temp() {
+        Ok(path) => path,
+        Err(e) => {
+            eprintln!("cannot compose {} drones into {}: {}", drones, scene, e);
+            return;
+        }
+    };
+    let session = session::SimSession::load(&path.to_string_lossy());
+    println!("{} drones composed into {}", drones, session.path.display());
+    let timestep = unsafe { (*session.simulation.model.ptr()).opt.timestep };
+    let actuator_num = unsafe { (*session.simulation.model.ptr()).nu };
+    let mut ctrl: Vec<f64> = vec![0.0; actuator_num as usize];
+
+    let mut vehicles: Vec<vehicle::Vehicle> =
+        composer.drones.iter().filter_map(|d| vehicle::Vehicle::new(&session.simulation, &d.prefix)).collect();
+    for (i, vehicle) in vehicles.iter_mut().enumerate() {
+        vehicle.sensors.seed(seed + i as u64);
+        vehicle.sensors.update(&session.simulation);
+        println!(
+            "{}x2: motors {:?}, {} cameras, {} lidar beams, mass {:.3}kg",
+            vehicle.prefix, vehicle.actuators, vehicle.cameras.len(), vehicle.lidar.len(), vehicle.controller.mass
+        );
+    }
+    let mut phases = vec![0; vehicles.len()];
+
+    // 3rd-person perspective and the front camera of the first three drones
+    let mut cam_ids = vec![0x7FFFFFFF];
+    cam_ids.extend(vehicles.iter().take(3).filter_map(|v| v.cameras.first().copied()));
+    let mut glfw = glfw::init(glfw::FAIL_ON_ERRORS).unwrap();
+    let mut ui_state = ui::ui_init(&mut glfw, &session.simulation, &cam_ids);
+
+    let mut step = 0;
+    while !ui_state.window.should_close() {
+        ui::update_scene(&session.simulation, &mut ui_state);
+
+        let time = unsafe { (*session.simulation.state.ptr()).time };
+        for ((vehicle, mission), phase) in vehicles.iter_mut().zip(missions.iter()).zip(phases.iter_mut()) {
+            let state = vehicle.state(&session.simulation, source);
+            if phase_done(mission[*phase], &state) && *phase + 1 < mission.len() {
+                println!("[{:.2}s] {}x2 {:?} done", time, vehicle.prefix, mission[*phase]);
+                *phase += 1;
+            }
+            vehicle.control(&state, &phase_setpoint(mission[*phase], &state), timestep, &mut ctrl);
+        }
+        session.simulation.control(&ctrl);
+        session.simulation.step();
+        for vehicle in vehicles.iter_mut() {
+            vehicle.sensors.update(&session.simulation);
+        }
+        step += 1;
+
+        if step % 100 == 0 {
+            for vehicle in vehicles.iter() {
+                let state = vehicle.state(&session.simulation, source);
+                // closest thing the lidar sees, often a neighbour
+                let nearest = vehicle.scan(&session.simulation).into_iter().filter(|&(_, range)| range >= 0.0).min_by(|a, b| a.1.total_cmp(&b.1));
+                let ctrl: Vec<f64> = vehicle.actuators.iter().map(|&a| ctrl[a]).collect();
+                match nearest {
+                    Some((bearing, range)) => println!(
+                        "{}x2 pos [{:.2}, {:.2}, {:.2}] ctrl {:.2?} nearest {:.2}m at {:.0}deg",
+                        vehicle.prefix, state.pos[0], state.pos[1], state.pos[2], ctrl, range, bearing
+                    ),
+                    None => println!("{}x2 pos [{:.2}, {:.2}, {:.2}] ctrl {:.2?}", vehicle.prefix, state.pos[0], state.pos[1], state.pos[2], ctrl),
+                }
+            }
+        }
+
+        glfw.poll_events();
+        for (_, event) in glfw::flush_messages(&ui_state.events) {
+            // restart the mission of every drone
+            if let glfw::WindowEvent::Key(glfw::Key::R, _, glfw::Action::Press, _) = event {
+                session.simulation.reset();
+                for vehicle in vehicles.iter_mut() {
+                    vehicle.reset(&session.simulation);
+                }
+                phases.fill(0);
+            }
+        }
+    }
+
+    ui::free_glfw(&mut ui_state);
+}
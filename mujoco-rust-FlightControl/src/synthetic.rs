@@ -0,0 +1,241 @@
+// synthetic.rs
+use crate::controller::quat_to_mat;
+use crate::imu::Imu;
+use mujoco_compose::rng::Rng;
+use mujoco_rs_sys::no_render;
+use mujoco_rust::Simulation;
+use std::collections::VecDeque;
+
+const EARTH_RADIUS: f64 = 6_378_137.0;
+
+/// Geodetic position of the world origin, x points east and y north
+#[derive(Debug, Clone, Copy)]
+pub struct GeoOrigin {
+    pub lat: f64, // deg
+    pub lon: f64, // deg
+    pub alt: f64, // m above MSL
+}
+
+impl Default for GeoOrigin {
+    fn default() -> Self {
+        // default home of PX4 SITL
+        GeoOrigin { lat: 47.397742, lon: 8.545594, alt: 488.0 }
+    }
+}
+
+impl GeoOrigin {
+    /// (lat, lon, alt) of a world position, flat earth around the origin
+    pub fn to_geodetic(&self, pos: &[f64; 3]) -> (f64, f64, f64) {
+        let lat = self.lat + (pos[1] / EARTH_RADIUS).to_degrees();
+        let lon = self.lon + (pos[0] / (EARTH_RADIUS * self.lat.to_radians().cos())).to_degrees();
+        (lat, lon, self.alt + pos[2])
+    }
+}
+
+/// Standard atmosphere pressure (hPa) at an altitude above MSL
+pub fn pressure(alt: f64) -> f64 {
+    1013.25 * (1.0 - 2.25577e-5 * alt).powf(5.25588)
+}
+
+/// GNSS receiver on a body
+///
+/// Values: `[lat, lon, alt, vel_e, vel_n, vel_u, fix]` in deg, m above MSL
+/// and m/s. Without a fix the last position is held and `fix` is 0.
+#[derive(Debug, Clone)]
+pub struct Gps {
+    pub home: GeoOrigin,
+    pub rate: f64,              // Hz
+    pub horizontal_noise: f64,  // m
+    pub vertical_noise: f64,    // m
+    pub velocity_noise: f64,    // m/s
+    pub latency: f64,           // s between measuring and reporting
+    pub fix_loss_rate: f64,     // fix losses per second
+    pub fix_loss_duration: f64, // s
+    pending: VecDeque<(f64, [f64; 6])>,
+    last_sample: f64,
+    lost_until: f64,
+}
+
+impl Default for Gps {
+    fn default() -> Self {
+        Gps {
+            home: GeoOrigin::default(),
+            rate: 10.0,
+            horizontal_noise: 0.5,
+            vertical_noise: 1.0,
+            velocity_noise: 0.05,
+            latency: 0.1,
+            fix_loss_rate: 0.0,
+            fix_loss_duration: 3.0,
+            pending: VecDeque::new(),
+            last_sample: f64::NEG_INFINITY,
+            lost_until: f64::NEG_INFINITY,
+        }
+    }
+}
+
+/// Barometer on a body
+///
+/// Values: `[pressure, alt]` in hPa and m above MSL. The altitude drifts
+/// as a random walk on top of the noise.
+#[derive(Debug, Clone)]
+pub struct Barometer {
+    pub home_alt: f64, // m above MSL of the world origin
+    pub noise: f64,    // m
+    pub drift: f64,    // m/sqrt(s)
+    bias: f64,
+    last_time: Option<f64>,
+}
+
+impl Default for Barometer {
+    fn default() -> Self {
+        Barometer { home_alt: GeoOrigin::default().alt, noise: 0.1, drift: 0.02, bias: 0.0, last_time: None }
+    }
+}
+
+/// Magnetometer on a body
+///
+/// Values: the Earth field in the body frame (gauss).
+#[derive(Debug, Clone)]
+pub struct Magnetometer {
+    pub field: [f64; 3], // world frame (x east, y north, z up)
+    pub noise: f64,      // gauss
+}
+
+impl Default for Magnetometer {
+    fn default() -> Self {
+        // roughly Zurich: 0.21 north, 0.015 east, 0.43 down
+        Magnetometer { field: [0.015, 0.21, -0.43], noise: 0.005 }
+    }
+}
+
+/// Sensor computed from the simulation instead of by MuJoCo
+#[derive(Debug, Clone)]
+pub enum Synthetic {
+    Gps(Gps),
+    Barometer(Barometer),
+    Magnetometer(Magnetometer),
+    Imu(Imu), // error model on a gyro or accelerometer of the model
+}
+
+impl Synthetic {
+    pub fn dim(&self) -> usize {
+        match self {
+            Synthetic::Gps(_) => 7,
+            Synthetic::Barometer(_) => 2,
+            Synthetic::Magnetometer(_) => 3,
+            Synthetic::Imu(_) => 3,
+        }
+    }
+
+    /// New values after a step, `values` keeps the old ones when the sensor
+    /// has no new reading
+    pub fn update(&mut self, simulation: &Simulation, body: usize, rng: &mut Rng, values: &mut [f64]) {
+        let pose = BodyPose::read(simulation, body);
+        match self {
+            Synthetic::Gps(gps) => {
+                // half a step, so float error in the time does not delay a
+                // sample or a report by a whole step
+                let tolerance = 0.5 * unsafe { (*simulation.model.ptr()).opt.timestep };
+                let period = 1.0 / gps.rate;
+                if pose.time - gps.last_sample >= period - tolerance {
+                    // samples stay on a fixed grid, the first one or one after
+                    // a gap starts it over
+                    gps.last_sample = if pose.time - gps.last_sample < 2.0 * period - tolerance {
+                        gps.last_sample + period
+                    } else {
+                        pose.time
+                    };
+                    if gps.fix_loss_rate > 0.0 && rng.uniform() < gps.fix_loss_rate / gps.rate {
+                        gps.lost_until = pose.time + gps.fix_loss_duration;
+                    }
+                    let h = rng.gaussian3(gps.horizontal_noise);
+                    let v = rng.gaussian3(gps.velocity_noise);
+                    let pos = [pose.pos[0] + h[0], pose.pos[1] + h[1], pose.pos[2] + rng.gaussian() * gps.vertical_noise];
+                    let (lat, lon, alt) = gps.home.to_geodetic(&pos);
+                    let sample = [lat, lon, alt, pose.vel[0] + v[0], pose.vel[1] + v[1], pose.vel[2] + v[2]];
+                    gps.pending.push_back((pose.time, sample));
+                }
+                // report the newest sample that is old enough
+                let mut reported = None;
+                while let Some(&(time, sample)) = gps.pending.front() {
+                    if pose.time - time < gps.latency - tolerance {
+                        break;
+                    }
+                    reported = Some(sample);
+                    gps.pending.pop_front();
+                }
+                // values are held between samples, no fix before the first one
+                if pose.time < gps.lost_until {
+                    values[6] = 0.0;
+                } else if let Some(sample) = reported {
+                    values[..6].copy_from_slice(&sample);
+                    values[6] = 1.0;
+                }
+            }
+            Synthetic::Barometer(baro) => {
+                let dt = pose.time - baro.last_time.unwrap_or(pose.time);
+                baro.last_time = Some(pose.time);
+                baro.bias += baro.drift * dt.max(0.0).sqrt() * rng.gaussian();
+                let alt = baro.home_alt + pose.pos[2] + baro.bias + rng.gaussian() * baro.noise;
+                values[0] = pressure(alt);
+                values[1] = alt;
+            }
+            Synthetic::Imu(imu) => imu.update(simulation, rng, values),
+            Synthetic::Magnetometer(mag) => {
+                let r = &pose.rot;
+                let f = &mag.field;
+                let noise = rng.gaussian3(mag.noise);
+                for i in 0..3 {
+                    values[i] = r[i] * f[0] + r[3 + i] * f[1] + r[6 + i] * f[2] + noise[i];
+                }
+            }
+        }
+    }
+
+    /// Start over, e.g. after a reset of the simulation
+    pub fn reset(&mut self) {
+        match self {
+            Synthetic::Gps(gps) => {
+                gps.pending.clear();
+                gps.last_sample = f64::NEG_INFINITY;
+                gps.lost_until = f64::NEG_INFINITY;
+            }
+            Synthetic::Barometer(baro) => {
+                baro.bias = 0.0;
+                baro.last_time = None;
+            }
+            Synthetic::Imu(imu) => imu.reset(),
+            Synthetic::Magnetometer(_) => {}
+        }
+    }
+}
+
+// ground truth of the body the sensor is on
+struct BodyPose {
+    time: f64,
+    pos: [f64; 3],
+    vel: [f64; 3],
+    rot: [f64; 9], // body -> world, row major
+}
+
+impl BodyPose {
+    fn read(simulation: &Simulation, body: usize) -> Self {
+        unsafe {
+            let m = simulation.model.ptr();
+            let d = simulation.state.ptr();
+            let mj_data = &*d;
+            let xpos = mj_data.xpos.add(3 * body);
+            let xquat = mj_data.xquat.add(4 * body);
+            // [angular, linear] at the center of mass, world frame
+            let mut velocity = [0.0; 6];
+            no_render::mj_objectVelocity(m, d, 1, body as i32, velocity.as_mut_ptr(), 0); // mjOBJ_BODY
+            BodyPose {
+                time: mj_data.time,
+                pos: [*xpos, *xpos.add(1), *xpos.add(2)],
+                vel: [velocity[3], velocity[4], velocity[5]],
+                rot: quat_to_mat(&[*xquat, *xquat.add(1), *xquat.add(2), *xquat.add(3)]),
+            }
+        }
+    }
+}
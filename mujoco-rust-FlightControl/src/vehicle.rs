@@ -0,0 +1,82 @@
+// vehicle.rs
+use crate::controller::{self, Command, Controller, DroneState, Setpoint, StateSource};
+use crate::mixer::Mixer;
+use crate::sensors::SensorRegistry;
+use mujoco_rust::model::ObjType;
+use mujoco_rust::Simulation;
+
+/// One drone of a model that may hold several, found by the prefix of its
+/// names (`d1_x2`, `d1_thrust1`, `d1_body_gyro`, ...)
+///
+/// Every vehicle has its own controller and mixer. The mixer only writes
+/// the ctrl of the vehicle's own actuators, so all vehicles share one ctrl
+/// vector and one `simulation.step()`.
+pub struct Vehicle {
+    pub prefix: String,
+    pub body: usize,
+    pub actuators: Vec<usize>,     // index in ctrl of each motor
+    pub cameras: Vec<i32>,         // `camera1..n` of the vehicle, ids for `ui::ui_init`
+    pub sensors: SensorRegistry,   // names without the prefix, e.g. `body_gyro`
+    pub lidar: Vec<(f64, usize)>,  // bearing (deg) & sensordata address of each `body_rf_<deg>`
+    pub controller: Controller,
+    pub mixer: Mixer,
+}
+
+impl Vehicle {
+    /// The drone with the body `<prefix>x2`, None if the model has none;
+    /// the prefix is empty for the single drone of `scene.xml`
+    pub fn new(simulation: &Simulation, prefix: &str) -> Option<Self> {
+        let body = simulation.model.name_to_id(ObjType::BODY, &format!("{}x2", prefix))? as usize;
+        let sensors = SensorRegistry::with_prefix(simulation, prefix);
+        let mut lidar: Vec<(f64, usize)> = sensors
+            .names()
+            .filter_map(|name| {
+                let bearing = name.strip_prefix("body_rf_")?.parse().ok()?;
+                Some((bearing, sensors.get(name)?.adr))
+            })
+            .collect();
+        lidar.sort_by(|a, b| a.0.total_cmp(&b.0));
+        let cameras = (1..)
+            .map_while(|i| simulation.model.name_to_id(ObjType::CAMERA, &format!("{}camera{}", prefix, i)))
+            .map(|id| id as i32)
+            .collect();
+        let mixer = Mixer::new(simulation, body);
+        let actuators = mixer.motors.iter().map(|m| m.actuator).collect();
+        Some(Vehicle {
+            prefix: prefix.to_string(),
+            body,
+            actuators,
+            cameras,
+            sensors,
+            lidar,
+            controller: Controller::new(simulation, body),
+            mixer,
+        })
+    }
+
+    pub fn state(&self, simulation: &Simulation, source: StateSource) -> DroneState {
+        controller::read_state(simulation, self.body, &self.sensors, source)
+    }
+
+    /// Run the controller and write the ctrl of this vehicle's actuators,
+    /// the flag is true when the command had to be reduced
+    pub fn control(&mut self, state: &DroneState, setpoint: &Setpoint, dt: f64, ctrl: &mut [f64]) -> (Command, bool) {
+        let command = self.controller.update(state, setpoint, dt);
+        let saturated = self.mixer.mix(&command, ctrl);
+        (command, saturated)
+    }
+
+    /// Bearing (deg) & range (m) of every lidar beam, -1 when nothing is
+    /// within the cutoff; the other vehicles are seen like any obstacle
+    pub fn scan(&self, simulation: &Simulation) -> Vec<(f64, f64)> {
+        let data = simulation.sensordata();
+        self.lidar.iter().map(|&(bearing, adr)| (bearing, data[adr])).collect()
+    }
+
+    /// Clear the controller & the synthetic sensors, e.g. after a reset
+    pub fn reset(&mut self, simulation: &Simulation) {
+        self.controller.reset();
+        self.sensors.reset();
+        self.sensors.update(simulation);
+    }
+}
@@ -0,0 +1,168 @@
+// wind.rs
+use crate::controller::quat_to_mat;
+use mujoco_compose::rng::Rng;
+use mujoco_rs_sys::no_render;
+use mujoco_rust::Simulation;
+use std::f64::consts::PI;
+
+const FEET: f64 = 0.3048; // m
+
+/// Scripted gust, 1-cosine shaped: 0 at `start`, `peak` halfway, 0 again
+/// after `duration`
+#[derive(Debug, Clone, Copy)]
+pub struct Gust {
+    pub start: f64,    // s
+    pub duration: f64, // s
+    pub peak: [f64; 3], // m/s, world frame
+}
+
+impl Gust {
+    pub fn velocity(&self, time: f64) -> [f64; 3] {
+        let t = time - self.start;
+        if t < 0.0 || t > self.duration {
+            return [0.0; 3];
+        }
+        let shape = 0.5 * (1.0 - (2.0 * PI * t / self.duration).cos());
+        self.peak.map(|p| p * shape)
+    }
+}
+
+/// Low altitude Dryden turbulence (MIL-F-8785C) from the wind speed at 6m
+///
+/// Intensities and length scales follow the height above ground, each axis
+/// is white noise through a first-order filter of its length scale (the
+/// lateral & vertical filters of the standard are second order). u & v are
+/// horizontal along world x & y, w vertical.
+#[derive(Debug, Clone)]
+pub struct Dryden {
+    pub wind_20ft: f64, // m/s, 7.5 light, 15 moderate, 23 severe
+    velocity: [f64; 3],
+}
+
+impl Dryden {
+    pub fn new(wind_20ft: f64) -> Self {
+        Dryden { wind_20ft, velocity: [0.0; 3] }
+    }
+
+    /// Advance by `dt` at `airspeed` (m/s) and `height` above ground (m)
+    pub fn update(&mut self, dt: f64, airspeed: f64, height: f64, rng: &mut Rng) -> [f64; 3] {
+        let h = (height / FEET).clamp(10.0, 1000.0); // ft, the model is for 10..1000ft
+        let sigma_w = 0.1 * self.wind_20ft;
+        let sigma_uv = sigma_w / (0.177 + 0.000823 * h).powf(0.4);
+        let length_w = h * FEET;
+        let length_uv = h / (0.177 + 0.000823 * h).powf(1.2) * FEET;
+
+        let sigma = [sigma_uv, sigma_uv, sigma_w];
+        let length = [length_uv, length_uv, length_w];
+        let airspeed = airspeed.max(1.0); // frozen turbulence needs some motion through it
+        for i in 0..3 {
+            let a = (-airspeed * dt / length[i]).exp();
+            self.velocity[i] = a * self.velocity[i] + sigma[i] * (1.0 - a * a).sqrt() * rng.gaussian();
+        }
+        self.velocity
+    }
+}
+
+/// Wind on a body: constant wind, turbulence and gusts, with quadratic
+/// drag of the body through the air
+///
+/// The drag is `½ρ CdA |v−w|(v−w)` per body axis, `v−w` the velocity of
+/// the body through the air. It replaces the drag of MuJoCo's fluid model
+/// (`density` & `viscosity` in x2.xml), which `new` switches off.
+pub struct Wind {
+    pub mean: [f64; 3], // m/s, world frame
+    pub turbulence: Option<Dryden>,
+    pub gusts: Vec<Gust>,
+    pub drag_area: [f64; 3], // Cd·A along body x, y, z (m²)
+    pub density: f64,        // kg/m³
+    rng: Rng,
+    velocity: [f64; 3],
+    force: [f64; 3],
+}
+
+impl Wind {
+    /// Still air, density of the model; MuJoCo stops adding drag of its own
+    pub fn new(simulation: &Simulation, seed: u64) -> Self {
+        let density = unsafe {
+            let opt = &mut (*simulation.model.ptr()).opt;
+            let density = opt.density;
+            opt.density = 0.0;
+            opt.viscosity = 0.0;
+            density
+        };
+        Wind {
+            mean: [0.0; 3],
+            turbulence: None,
+            gusts: Vec::new(),
+            drag_area: [0.02, 0.02, 0.05],
+            density,
+            rng: Rng::new(seed),
+            velocity: [0.0; 3],
+            force: [0.0; 3],
+        }
+    }
+
+    /// Wind at the body after the last `apply`, world frame
+    pub fn velocity(&self) -> [f64; 3] {
+        self.velocity
+    }
+
+    /// Drag force of the last `apply`, world frame
+    pub fn force(&self) -> [f64; 3] {
+        self.force
+    }
+
+    /// Update the wind over `dt` and add the drag to `xfrc_applied` of the
+    /// body, call before every `simulation.step()` after `clear_applied`
+    pub fn apply(&mut self, simulation: &Simulation, body: usize, dt: f64) {
+        unsafe {
+            let m = simulation.model.ptr();
+            let d = simulation.state.ptr();
+            let mj_data = &mut *d;
+            let time = mj_data.time;
+            let xquat = std::slice::from_raw_parts(mj_data.xquat.add(4 * body), 4);
+            let rot = quat_to_mat(&[xquat[0], xquat[1], xquat[2], xquat[3]]);
+            let height = *mj_data.xipos.add(3 * body + 2);
+            // [angular, linear] at the center of mass, world frame
+            let mut velocity = [0.0; 6];
+            no_render::mj_objectVelocity(m, d, 1, body as i32, velocity.as_mut_ptr(), 0); // mjOBJ_BODY
+            let vel = [velocity[3], velocity[4], velocity[5]];
+
+            let mut wind = self.mean;
+            for gust in self.gusts.iter() {
+                let g = gust.velocity(time);
+                (0..3).for_each(|i| wind[i] += g[i]);
+            }
+            if let Some(turbulence) = self.turbulence.as_mut() {
+                let relative = (0..3).map(|i| (vel[i] - wind[i]).powi(2)).sum::<f64>().sqrt();
+                let t = turbulence.update(dt, relative, height, &mut self.rng);
+                (0..3).for_each(|i| wind[i] += t[i]);
+            }
+            self.velocity = wind;
+
+            // body frame velocity through the air
+            let air = mat_t_vec(&rot, &[vel[0] - wind[0], vel[1] - wind[1], vel[2] - wind[2]]);
+            let air_speed = (air[0] * air[0] + air[1] * air[1] + air[2] * air[2]).sqrt();
+            let mut drag = [0.0; 3];
+            for i in 0..3 {
+                drag[i] = -0.5 * self.density * self.drag_area[i] * air_speed * air[i];
+            }
+            let force = [
+                rot[0] * drag[0] + rot[1] * drag[1] + rot[2] * drag[2],
+                rot[3] * drag[0] + rot[4] * drag[1] + rot[5] * drag[2],
+                rot[6] * drag[0] + rot[7] * drag[1] + rot[8] * drag[2],
+            ];
+            let xfrc = std::slice::from_raw_parts_mut(mj_data.xfrc_applied.add(6 * body), 3);
+            (0..3).for_each(|i| xfrc[i] += force[i]);
+            self.force = force;
+        }
+    }
+}
+
+fn mat_t_vec(m: &[f64; 9], v: &[f64; 3]) -> [f64; 3] {
+    [
+        m[0] * v[0] + m[3] * v[1] + m[6] * v[2],
+        m[1] * v[0] + m[4] * v[1] + m[7] * v[2],
+        m[2] * v[0] + m[5] * v[1] + m[8] * v[2],
+    ]
+}
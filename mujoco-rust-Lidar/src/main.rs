@@ -1,18 +1,38 @@
 // main.rs
 mod ui;
 mod lidar;
+mod plot;
+
+use minifb::{Key, KeyRepeat};
+use mujoco_compose::actors;
 
 
 
 fn main() -> Result<(), Box<dyn std::error::Error>> {
 
+    // usage: mujoco-rust-Lidar [--scene <file>], e.g. ../x2/street_scene.xml
+    let mut scene = "../x2/scene.xml".to_string();
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--scene" => scene = args.next().unwrap_or(scene),
+            _ => eprintln!("unknown argument: {}", arg),
+        }
+    }
+
     println!("Sim Start...");
     println!("--------------------------------------------------");
 
     // init model
-    let model = mujoco_rust::Model::from_xml("../x2/scene.xml".to_string()).unwrap();
+    let model = mujoco_rust::Model::from_xml(scene).unwrap();
     let simulation = mujoco_rust::Simulation::new(model.clone());
 
+    // moving vehicles of the street scene, none in the other scenes
+    let traffic = actors::street_traffic(&simulation);
+    for actor in traffic.actors.iter() {
+        println!("{} moves along a {:.0}m route at {}m/s", actor.name, actor.route.total_length(), actor.route.speed);
+    }
+
     // init ctrl vector 
     let actuator_num = unsafe { (*simulation.model.ptr()).nu };
     let mut ctrl: Vec<f64> = vec![0.0; actuator_num as usize]; 
@@ -25,6 +45,9 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
     // let mj_model = unsafe { *model.ptr() };
     println!("{}", simulation.model.geoms().iter().map(|g| g.name.to_string()).collect::<Vec<_>>().join(", "));
     
+    // get drone body id for the heading
+    let x2_id = lidar::get_body_id(&model, "x2");
+
     // get Lidar sensor ids
     let rf_ids= lidar::get_lidar_id(model);
 
@@ -34,11 +57,25 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
     let mut lidar_window = lidar::init_lidar_window(lidar_width, lidar_height)?;
     let mut lidar_buffer: Vec<u32> = vec![0; lidar_width * lidar_height];
 
+    // polar plot: 10m range, fading trail of the last 5 scans
+    let mut lidar_plot = plot::PolarPlot::new(lidar_width, lidar_height, 10.0);
+    lidar_plot.trail_len = 5;
+
 
     // sim running until the window closes
     while !ui_state.window.should_close() {
 
-        lidar::update_lidar_buffer(lidar_width, lidar_height, &mut lidar_buffer, &rf_ids, &simulation);
+        // press N to switch between heading-up and north-up
+        if lidar_window.is_key_pressed(Key::N, KeyRepeat::No) {
+            lidar_plot.toggle_orientation();
+        }
+        // press C to cycle the distance colour map
+        if lidar_window.is_key_pressed(Key::C, KeyRepeat::No) {
+            lidar_plot.cycle_color_map();
+        }
+
+        let heading = lidar::get_heading(&simulation, x2_id);
+        lidar::update_lidar_buffer(&mut lidar_plot, &mut lidar_buffer, &rf_ids, heading, &simulation);
         // update Lidar window
         lidar_window.update_with_buffer(&lidar_buffer, lidar_width, lidar_height)?;
         
@@ -48,6 +85,7 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
         // ctrl array fixed settings
         ctrl[..4].fill(4.5);
         simulation.control(&ctrl);
+        traffic.update(&simulation);
 
         // sim forward a step
         simulation.step();
@@ -0,0 +1,384 @@
+// plot.rs
+use std::collections::VecDeque;
+use std::f64::consts::PI;
+
+// 3x5 bitmap glyphs, one row per byte, bit 2 is the left column
+const GLYPH_W: i32 = 3;
+const GLYPH_H: i32 = 5;
+
+fn glyph(c: char) -> [u8; 5] {
+    match c {
+        '0' => [0b111, 0b101, 0b101, 0b101, 0b111],
+        '1' => [0b010, 0b110, 0b010, 0b010, 0b111],
+        '2' => [0b111, 0b001, 0b111, 0b100, 0b111],
+        '3' => [0b111, 0b001, 0b111, 0b001, 0b111],
+        '4' => [0b101, 0b101, 0b111, 0b001, 0b001],
+        '5' | 'S' => [0b111, 0b100, 0b111, 0b001, 0b111],
+        '6' => [0b111, 0b100, 0b111, 0b101, 0b111],
+        '7' => [0b111, 0b001, 0b001, 0b001, 0b001],
+        '8' => [0b111, 0b101, 0b111, 0b101, 0b111],
+        '9' => [0b111, 0b101, 0b111, 0b001, 0b111],
+        'm' => [0b000, 0b111, 0b111, 0b101, 0b101],
+        'N' => [0b101, 0b111, 0b111, 0b101, 0b101],
+        'E' => [0b111, 0b100, 0b111, 0b100, 0b111],
+        'W' => [0b101, 0b101, 0b111, 0b111, 0b101],
+        '.' => [0b000, 0b000, 0b000, 0b000, 0b010],
+        '-' => [0b000, 0b000, 0b111, 0b000, 0b000],
+        _ => [0; 5],
+    }
+}
+
+/// Which direction is drawn at the top of the plot
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Orientation {
+    /// vehicle nose up, bearings relative to the body (clockwise)
+    HeadingUp,
+    /// world +y up, compass bearings (clockwise from north)
+    NorthUp,
+}
+
+/// Maps a normalized distance in [0, 1] to a pixel color
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum ColorMap {
+    Solid(u32),
+    /// near = red, far = blue
+    Jet,
+    /// near = black, far = light grey
+    Grayscale,
+}
+
+impl ColorMap {
+    pub fn color(&self, t: f64) -> u32 {
+        let t = t.clamp(0.0, 1.0);
+        match *self {
+            ColorMap::Solid(color) => color,
+            ColorMap::Jet => {
+                // reversed jet so that close obstacles stand out
+                let v = 1.0 - t;
+                let r = (1.5 - (4.0 * v - 3.0).abs()).clamp(0.0, 1.0);
+                let g = (1.5 - (4.0 * v - 2.0).abs()).clamp(0.0, 1.0);
+                let b = (1.5 - (4.0 * v - 1.0).abs()).clamp(0.0, 1.0);
+                rgb(r, g, b)
+            }
+            ColorMap::Grayscale => {
+                let v = 0.8 * t;
+                rgb(v, v, v)
+            }
+        }
+    }
+}
+
+fn rgb(r: f64, g: f64, b: f64) -> u32 {
+    let r = (r * 255.0).round() as u32;
+    let g = (g * 255.0).round() as u32;
+    let b = (b * 255.0).round() as u32;
+    (r << 16) | (g << 8) | b
+}
+
+// blend a color towards the background, used to fade old trail points
+fn fade(color: u32, background: u32, alpha: f64) -> u32 {
+    let mix = |shift: u32| {
+        let c = ((color >> shift) & 0xFF) as f64;
+        let bg = ((background >> shift) & 0xFF) as f64;
+        ((bg + (c - bg) * alpha).round() as u32) << shift
+    };
+    mix(16) | mix(8) | mix(0)
+}
+
+/// Polar plot of (angle, range) samples, e.g. a scan of rangefinders
+pub struct PolarPlot {
+    pub width: usize,
+    pub height: usize,
+    pub max_range: f64,       // outer ring (m)
+    pub ring_step: f64,       // distance between range rings (m)
+    pub spoke_step: u32,      // angle between bearing spokes (deg)
+    pub orientation: Orientation,
+    pub color_map: ColorMap,
+    pub point_radius: f64,    // px
+    pub trail_len: usize,     // number of previous scans kept on screen
+    pub background: u32,
+    pub ring_color: u32,
+    pub spoke_color: u32,
+    pub label_color: u32,
+    pub heading_color: u32,
+    trail: VecDeque<Vec<(f64, f64)>>,
+}
+
+impl PolarPlot {
+    pub fn new(width: usize, height: usize, max_range: f64) -> Self {
+        PolarPlot {
+            width,
+            height,
+            max_range,
+            ring_step: 1.0,
+            spoke_step: 30,
+            orientation: Orientation::HeadingUp,
+            color_map: ColorMap::Jet,
+            point_radius: 3.0,
+            trail_len: 0,
+            background: 0xFFFFFF,
+            ring_color: 0x800000,
+            spoke_color: 0x000000,
+            label_color: 0x000000,
+            heading_color: 0x0000FF,
+            trail: VecDeque::new(),
+        }
+    }
+
+    pub fn toggle_orientation(&mut self) {
+        self.orientation = match self.orientation {
+            Orientation::HeadingUp => Orientation::NorthUp,
+            Orientation::NorthUp => Orientation::HeadingUp,
+        };
+        // old points were drawn in the other frame
+        self.trail.clear();
+    }
+
+    pub fn cycle_color_map(&mut self) {
+        self.color_map = match self.color_map {
+            ColorMap::Jet => ColorMap::Grayscale,
+            ColorMap::Grayscale => ColorMap::Solid(0xFF0000),
+            ColorMap::Solid(_) => ColorMap::Jet,
+        };
+    }
+
+    // px per meter so that the outer ring and its labels fit in the window
+    fn pixels_per_meter(&self) -> f64 {
+        let margin = 10.0 + 3.0 * GLYPH_H as f64 * 2.0;
+        (self.width.min(self.height) as f64 / 2.0 - margin) / self.max_range
+    }
+
+    fn center(&self) -> (f64, f64) {
+        (self.width as f64 / 2.0, self.height as f64 / 2.0)
+    }
+
+    // bearing (rad, clockwise from the top of the plot) & radius (px) to pixel
+    fn to_pixel(&self, bearing: f64, radius: f64) -> (f64, f64) {
+        let (cx, cy) = self.center();
+        (cx + radius * bearing.sin(), cy - radius * bearing.cos())
+    }
+
+    // body-frame angle (rad, counter-clockwise from the nose) to plot bearing
+    fn to_bearing(&self, angle: f64, heading: f64) -> f64 {
+        match self.orientation {
+            Orientation::HeadingUp => -angle,
+            Orientation::NorthUp => PI / 2.0 - (angle + heading),
+        }
+    }
+
+    /// Redraw the whole plot.
+    ///
+    /// `scan` holds (angle, range) pairs in the sensor frame, angle in rad
+    /// counter-clockwise from the vehicle nose, range in m. `heading` is the
+    /// vehicle yaw in the world frame (rad, counter-clockwise from +x) and is
+    /// only used in north-up mode. Samples outside [0, max_range] are dropped.
+    pub fn draw(&mut self, buffer: &mut [u32], scan: &[(f64, f64)], heading: f64) {
+        buffer.fill(self.background);
+        self.draw_grid(buffer, heading);
+
+        let points: Vec<(f64, f64)> = scan
+            .iter()
+            .filter(|(_, r)| *r >= 0.0 && *r <= self.max_range)
+            .map(|&(angle, r)| (self.to_bearing(angle, heading), r))
+            .collect();
+
+        // older scans first so that the newest one ends up on top
+        let trail_count = self.trail.len();
+        for (age, old) in self.trail.iter().enumerate() {
+            let alpha = (age + 1) as f64 / (trail_count + 1) as f64;
+            for &(bearing, r) in old {
+                let color = fade(self.color_map.color(r / self.max_range), self.background, alpha);
+                self.draw_point(buffer, bearing, r, self.point_radius - 1.0, color);
+            }
+        }
+        for &(bearing, r) in &points {
+            let color = self.color_map.color(r / self.max_range);
+            self.draw_point(buffer, bearing, r, self.point_radius, color);
+        }
+
+        if self.trail_len > 0 {
+            self.trail.push_back(points);
+            while self.trail.len() > self.trail_len {
+                self.trail.pop_front();
+            }
+        }
+    }
+
+    fn draw_point(&self, buffer: &mut [u32], bearing: f64, range: f64, radius: f64, color: u32) {
+        let (x, y) = self.to_pixel(bearing, range * self.pixels_per_meter());
+        draw_circle(buffer, self.width, self.height, x, y, radius.max(1.0), color);
+    }
+
+    fn draw_grid(&self, buffer: &mut [u32], heading: f64) {
+        let (cx, cy) = self.center();
+        let ppm = self.pixels_per_meter();
+        let outer = self.max_range * ppm;
+
+        // range rings, labelled along the 45° diagonal
+        let rings = (self.max_range / self.ring_step).floor() as usize;
+        for i in 1..=rings {
+            let r = i as f64 * self.ring_step;
+            draw_circle_outline(buffer, self.width, self.height, cx, cy, r * ppm, self.ring_color);
+            let (x, y) = self.to_pixel(PI / 4.0, r * ppm);
+            draw_text(buffer, self.width, self.height, x + 3.0, y - 12.0, &format_range(r), self.label_color, 2);
+        }
+
+        // bearing spokes & labels outside the outer ring
+        let label_radius = outer + 2.0 * GLYPH_H as f64 + 4.0;
+        for deg in (0..360).step_by(self.spoke_step.max(1) as usize) {
+            let bearing = deg as f64 * PI / 180.0;
+            let (x, y) = self.to_pixel(bearing, outer);
+            draw_line(buffer, self.width, self.height, cx, cy, x, y, self.spoke_color);
+
+            let label = match (self.orientation, deg) {
+                (Orientation::NorthUp, 0) => "N".to_string(),
+                (Orientation::NorthUp, 90) => "E".to_string(),
+                (Orientation::NorthUp, 180) => "S".to_string(),
+                (Orientation::NorthUp, 270) => "W".to_string(),
+                _ => deg.to_string(),
+            };
+            let (lx, ly) = self.to_pixel(bearing, label_radius);
+            draw_text_centered(buffer, self.width, self.height, lx, ly, &label, self.label_color, 2);
+        }
+
+        match self.orientation {
+            // vehicle nose
+            Orientation::NorthUp => {
+                let (x, y) = self.to_pixel(self.to_bearing(0.0, heading), outer);
+                draw_line(buffer, self.width, self.height, cx, cy, x, y, self.heading_color);
+            }
+            // where north is relative to the nose
+            Orientation::HeadingUp => {
+                let north = heading - PI / 2.0;
+                let (x, y) = self.to_pixel(north, outer - 2.0 * GLYPH_H as f64 - 4.0);
+                draw_text_centered(buffer, self.width, self.height, x, y, "N", self.heading_color, 2);
+            }
+        }
+    }
+}
+
+fn format_range(r: f64) -> String {
+    if r.fract() == 0.0 {
+        format!("{}m", r as i64)
+    } else {
+        format!("{:.1}m", r)
+    }
+}
+
+/// Draw `text` with its top-left corner at (x, y), glyphs scaled by `scale`
+pub fn draw_text(buffer: &mut [u32], width: usize, height: usize, x: f64, y: f64, text: &str, color: u32, scale: i32) {
+    let x0 = x.round() as i32;
+    let y0 = y.round() as i32;
+    for (i, c) in text.chars().enumerate() {
+        let rows = glyph(c);
+        let gx = x0 + i as i32 * (GLYPH_W + 1) * scale;
+        for (row, bits) in rows.iter().enumerate() {
+            for col in 0..GLYPH_W {
+                if bits & (1 << (GLYPH_W - 1 - col)) == 0 {
+                    continue;
+                }
+                for sy in 0..scale {
+                    for sx in 0..scale {
+                        let px = gx + col * scale + sx;
+                        let py = y0 + row as i32 * scale + sy;
+                        if px >= 0 && px < width as i32 && py >= 0 && py < height as i32 {
+                            buffer[(py * width as i32 + px) as usize] = color;
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// Draw `text` centered on (x, y)
+pub fn draw_text_centered(buffer: &mut [u32], width: usize, height: usize, x: f64, y: f64, text: &str, color: u32, scale: i32) {
+    let n = text.chars().count() as i32;
+    let text_w = (n * (GLYPH_W + 1) - 1) * scale;
+    let text_h = GLYPH_H * scale;
+    draw_text(buffer, width, height, x - text_w as f64 / 2.0, y - text_h as f64 / 2.0, text, color, scale);
+}
+
+pub fn draw_circle(buffer: &mut [u32], width: usize, height: usize, cx: f64, cy: f64, radius: f64, color: u32) {
+    let cx = cx.round() as i32;
+    let cy = cy.round() as i32;
+    let radius = radius.round() as i32;
+    for y in (cy - radius).max(0)..(cy + radius + 1).min(height as i32) {
+        for x in (cx - radius).max(0)..(cx + radius + 1).min(width as i32) {
+            let dx = x - cx;
+            let dy = y - cy;
+            if dx * dx + dy * dy <= radius * radius {
+                buffer[(y * width as i32 + x) as usize] = color;
+            }
+        }
+    }
+}
+
+
+//Draw hollow rings
+pub fn draw_circle_outline(buffer: &mut [u32], width: usize, height: usize, cx: f64, cy: f64, radius: f64, color: u32) {
+    let cx = cx.round() as i32;
+    let cy = cy.round() as i32;
+    let radius = radius.round() as i32;
+    let mut x = radius;
+    let mut y = 0;
+    let mut err = 0;
+
+    while x >= y {
+        let points = [
+            (cx + x, cy + y),
+            (cx + y, cy + x),
+            (cx - y, cy + x),
+            (cx - x, cy + y),
+            (cx - x, cy - y),
+            (cx - y, cy - x),
+            (cx + y, cy - x),
+            (cx + x, cy - y),
+        ];
+
+        for (px, py) in points.iter() {
+            if *px >= 0 && *px < width as i32 && *py >= 0 && *py < height as i32 {
+                buffer[(*py * width as i32 + *px) as usize] = color;
+            }
+        }
+
+        y += 1;
+        err += 1 + 2 * y;
+        if 2 * (err - x) + 1 > 0 {
+            x -= 1;
+            err += 1 - 2 * x;
+        }
+    }
+}
+
+//Draw lines in Bresenham algorithm
+pub fn draw_line(buffer: &mut [u32], width: usize, height: usize, x1: f64, y1: f64, x2: f64, y2: f64, color: u32) {
+    let mut x1 = x1.round() as i32;
+    let mut y1 = y1.round() as i32;
+    let x2 = x2.round() as i32;
+    let y2 = y2.round() as i32;
+
+    let dx = (x2 - x1).abs();
+    let dy = (y2 - y1).abs();
+    let sx = if x1 < x2 { 1 } else { -1 };
+    let sy = if y1 < y2 { 1 } else { -1 };
+    let mut err = dx - dy;
+
+    loop {
+        if x1 >= 0 && x1 < width as i32 && y1 >= 0 && y1 < height as i32 {
+            buffer[(y1 * width as i32 + x1) as usize] = color;
+        }
+        if x1 == x2 && y1 == y2 {
+            break;
+        }
+        let e2 = 2 * err;
+        if e2 > -dy {
+            err -= dy;
+            x1 += sx;
+        }
+        if e2 < dx {
+            err += dx;
+            y1 += sy;
+        }
+    }
+}
@@ -0,0 +1,312 @@
+// collision.rs
+use crate::contact::{geom_body, geom_name, label};
+use mujoco_rs_sys::no_render;
+use mujoco_rust::model::ObjType;
+use mujoco_rust::Simulation;
+use std::collections::BTreeSet;
+use std::fmt;
+
+// contype/conaffinity bit used to force a pair through the filter in queries
+const QUERY_BIT: i32 = 1 << 30;
+// mjDSBL_FILTERPARENT
+const DISABLE_FILTERPARENT: i32 = 1 << 9;
+
+/// Two geoms that MuJoCo will check for contacts
+#[derive(Debug, Clone)]
+pub struct CollisionPair {
+    pub geom1: usize,
+    pub geom2: usize,
+    pub geom1_name: String,
+    pub geom2_name: String,
+    pub explicit: bool, // defined by a <pair> element instead of contype/conaffinity
+}
+
+impl fmt::Display for CollisionPair {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} <-> {}{}",
+            label(&self.geom1_name, "geom", self.geom1),
+            label(&self.geom2_name, "geom", self.geom2),
+            if self.explicit { " (pair)" } else { "" }
+        )
+    }
+}
+
+pub fn geom_id(simulation: &Simulation, name: &str) -> Option<usize> {
+    simulation.model.name_to_id(ObjType::GEOM, name).map(|id| id as usize)
+}
+
+/// (contype, conaffinity) of a geom
+pub fn collision_filter(simulation: &Simulation, geom: usize) -> (i32, i32) {
+    unsafe {
+        let mj_model = &*simulation.model.ptr();
+        (*mj_model.geom_contype.add(geom), *mj_model.geom_conaffinity.add(geom))
+    }
+}
+
+/// Change contype/conaffinity of a geom on the live model, takes effect at
+/// the next step. Setting both to 0 disables collisions of the geom.
+pub fn set_collision_filter(simulation: &Simulation, geom: usize, contype: i32, conaffinity: i32) {
+    unsafe {
+        let mj_model = &*simulation.model.ptr();
+        *mj_model.geom_contype.add(geom) = contype;
+        *mj_model.geom_conaffinity.add(geom) = conaffinity;
+    }
+}
+
+// the body filters of mj_collision: same weld body, parent-child & <exclude>
+fn bodies_filtered(simulation: &Simulation, geom1: usize, geom2: usize) -> bool {
+    unsafe {
+        let mj_model = &*simulation.model.ptr();
+        let body1 = geom_body(simulation, geom1);
+        let body2 = geom_body(simulation, geom2);
+        let weld1 = *mj_model.body_weldid.add(body1);
+        let weld2 = *mj_model.body_weldid.add(body2);
+        if weld1 == weld2 {
+            return true;
+        }
+
+        let weld_parent1 = *mj_model.body_weldid.add(*mj_model.body_parentid.add(weld1 as usize) as usize);
+        let weld_parent2 = *mj_model.body_weldid.add(*mj_model.body_parentid.add(weld2 as usize) as usize);
+        let filter_parent = mj_model.opt.disableflags & DISABLE_FILTERPARENT == 0;
+        if filter_parent && weld1 != 0 && weld2 != 0 && (weld1 == weld_parent2 || weld2 == weld_parent1) {
+            return true;
+        }
+
+        let signature = ((body1.min(body2) as i32) << 16) + body1.max(body2) as i32;
+        (0..mj_model.nexclude as usize).any(|i| *mj_model.exclude_signature.add(i) == signature)
+    }
+}
+
+/// All geom pairs that can produce contacts with the current filters
+pub fn collision_pairs(simulation: &Simulation) -> Vec<CollisionPair> {
+    let mut pairs = Vec::new();
+    let mut explicit = BTreeSet::new();
+    let (ngeom, npair) = unsafe {
+        let mj_model = &*simulation.model.ptr();
+        for i in 0..mj_model.npair as usize {
+            let g1 = *mj_model.pair_geom1.add(i) as usize;
+            let g2 = *mj_model.pair_geom2.add(i) as usize;
+            explicit.insert((g1.min(g2), g1.max(g2)));
+        }
+        (mj_model.ngeom as usize, mj_model.npair)
+    };
+
+    for geom1 in 0..ngeom {
+        for geom2 in geom1 + 1..ngeom {
+            let is_explicit = npair > 0 && explicit.contains(&(geom1, geom2));
+            let (contype1, conaffinity1) = collision_filter(simulation, geom1);
+            let (contype2, conaffinity2) = collision_filter(simulation, geom2);
+            let compatible = (contype1 & conaffinity2) != 0 || (contype2 & conaffinity1) != 0;
+            if is_explicit || (compatible && !bodies_filtered(simulation, geom1, geom2)) {
+                pairs.push(CollisionPair {
+                    geom1,
+                    geom2,
+                    geom1_name: geom_name(simulation, geom1),
+                    geom2_name: geom_name(simulation, geom2),
+                    explicit: is_explicit,
+                });
+            }
+        }
+    }
+    pairs
+}
+
+fn is_plane(simulation: &Simulation, geom: usize) -> bool {
+    unsafe { *(*simulation.model.ptr()).geom_type.add(geom) == 0 } // 0: mjGEOM_PLANE
+}
+
+/// Nearest point of another geom within the query distance
+#[derive(Debug, Clone)]
+pub struct Proximity {
+    pub geom: usize,
+    pub geom_name: String,
+    pub other: usize,
+    pub other_name: String,
+    pub dist: f64,      // signed, negative means penetration
+    pub pos: [f64; 3],  // midpoint between the two surfaces
+}
+
+// run the narrow phase on a copy of mj_data with larger margins for `geoms`,
+// contacts are then reported up to `distmax` away with their signed distance
+// (MuJoCo 2.3.5 has no mj_geomDistance). `scratch` is overwritten. Geoms that
+// never collide (contype & conaffinity 0, e.g. visual meshes) are left out.
+fn query_contacts(
+    simulation: &Simulation,
+    scratch: *mut no_render::mjData_,
+    geoms: &[usize],
+    partners: &[usize],
+    distmax: f64,
+) -> Vec<(usize, usize, f64, [f64; 3])> {
+    let mut found = Vec::new();
+    let collides = |&g: &usize| {
+        let (contype, conaffinity) = collision_filter(simulation, g);
+        contype | conaffinity != 0
+    };
+    let geoms: Vec<usize> = geoms.iter().copied().filter(collides).collect();
+    let partners: Vec<usize> = partners.iter().copied().filter(collides).collect();
+    unsafe {
+        let m = simulation.model.ptr();
+        let mj_model = &*m;
+
+        // save & widen margins, let the pairs through the filter
+        let saved: Vec<(usize, f64, i32, i32)> = geoms
+            .iter()
+            .chain(partners.iter())
+            .map(|&g| {
+                let (contype, conaffinity) = collision_filter(simulation, g);
+                (g, *mj_model.geom_margin.add(g), contype, conaffinity)
+            })
+            .collect();
+        for &g in geoms.iter() {
+            *mj_model.geom_margin.add(g) = distmax;
+            *mj_model.geom_contype.add(g) |= QUERY_BIT;
+        }
+        for &g in partners.iter() {
+            *mj_model.geom_conaffinity.add(g) |= QUERY_BIT;
+        }
+
+        no_render::mj_copyData(scratch, m, simulation.state.ptr());
+        no_render::mj_kinematics(m, scratch);
+        no_render::mj_collision(m, scratch);
+
+        let data = &*scratch;
+        for i in 0..data.ncon as usize {
+            let contact = &*data.contact.add(i);
+            let (g1, g2) = (contact.geom1 as usize, contact.geom2 as usize);
+            let pair = if geoms.contains(&g1) && partners.contains(&g2) {
+                Some((g1, g2))
+            } else if geoms.contains(&g2) && partners.contains(&g1) {
+                Some((g2, g1))
+            } else {
+                None
+            };
+            if let Some((geom, other)) = pair {
+                if contact.dist <= distmax {
+                    found.push((geom, other, contact.dist, contact.pos));
+                }
+            }
+        }
+
+        // restore the model
+        for (g, margin, contype, conaffinity) in saved.into_iter().rev() {
+            *mj_model.geom_margin.add(g) = margin;
+            *mj_model.geom_contype.add(g) = contype;
+            *mj_model.geom_conaffinity.add(g) = conaffinity;
+        }
+    }
+    found
+}
+
+/// Signed distances between geoms, with an mj_data of its own to run the
+/// queries on
+pub struct GeomDistance {
+    scratch: *mut no_render::mjData_, // reused by every query
+}
+
+impl GeomDistance {
+    pub fn new(simulation: &Simulation) -> Self {
+        GeomDistance { scratch: unsafe { no_render::mj_makeData(simulation.model.ptr()) } }
+    }
+
+    /// Distance between two geoms if it is below `distmax`, negative when
+    /// they penetrate. Geoms of the same body are never checked by MuJoCo,
+    /// nor geoms that do not collide.
+    pub fn query(&self, simulation: &Simulation, geom1: usize, geom2: usize, distmax: f64) -> Option<f64> {
+        query_contacts(simulation, self.scratch, &[geom1], &[geom2], distmax)
+            .into_iter()
+            .map(|(_, _, dist, _)| dist)
+            .reduce(f64::min)
+    }
+}
+
+impl Drop for GeomDistance {
+    fn drop(&mut self) {
+        unsafe {
+            no_render::mj_deleteData(self.scratch);
+        }
+    }
+}
+
+// for every geom of `body`, the nearest geom of another body within `distmax`
+fn proximity(simulation: &Simulation, scratch: *mut no_render::mjData_, body: usize, distmax: f64) -> Vec<Proximity> {
+    let ngeom = unsafe { (*simulation.model.ptr()).ngeom as usize };
+    let (geoms, partners): (Vec<usize>, Vec<usize>) = (0..ngeom).partition(|&g| geom_body(simulation, g) == body);
+
+    let mut nearest: Vec<Proximity> = Vec::new();
+    for (geom, other, dist, pos) in query_contacts(simulation, scratch, &geoms, &partners, distmax) {
+        match nearest.iter_mut().find(|p| p.geom == geom) {
+            Some(p) if p.dist <= dist => {}
+            Some(p) => {
+                p.other = other;
+                p.other_name = geom_name(simulation, other);
+                p.dist = dist;
+                p.pos = pos;
+            }
+            None => nearest.push(Proximity {
+                geom,
+                geom_name: geom_name(simulation, geom),
+                other,
+                other_name: geom_name(simulation, other),
+                dist,
+                pos,
+            }),
+        }
+    }
+    nearest
+}
+
+/// Prints a warning when a geom of the body gets closer than `warn_distance`
+/// to something, before the actual contact
+pub struct ProximityMonitor {
+    pub body: usize,
+    pub warn_distance: f64,
+    near: BTreeSet<(usize, usize)>,
+    scratch: *mut no_render::mjData_, // reused by every update
+}
+
+impl ProximityMonitor {
+    pub fn new(simulation: &Simulation, body_name: &str, warn_distance: f64) -> Self {
+        let body = simulation.model.name_to_id(ObjType::BODY, body_name).unwrap() as usize;
+        let scratch = unsafe { no_render::mj_makeData(simulation.model.ptr()) };
+        ProximityMonitor { body, warn_distance, near: BTreeSet::new(), scratch }
+    }
+
+    /// Geoms that came within the warning distance since the last update
+    pub fn update(&mut self, simulation: &Simulation) -> Vec<Proximity> {
+        // only obstacles that are not touched yet, flying low over the
+        // floor is not worth a warning and contacts are reported by the tracker
+        let current: Vec<Proximity> = proximity(simulation, self.scratch, self.body, self.warn_distance)
+            .into_iter()
+            .filter(|p| p.dist > 0.0 && !is_plane(simulation, p.other))
+            .collect();
+        let keys: BTreeSet<(usize, usize)> = current.iter().map(|p| (p.geom, p.other)).collect();
+        let new = current.into_iter().filter(|p| !self.near.contains(&(p.geom, p.other))).collect();
+        self.near = keys;
+        new
+    }
+}
+
+impl Drop for ProximityMonitor {
+    fn drop(&mut self) {
+        unsafe {
+            no_render::mj_deleteData(self.scratch);
+        }
+    }
+}
+
+impl fmt::Display for Proximity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} is {:.3}m from {} at [{:.3}, {:.3}, {:.3}]",
+            label(&self.geom_name, "geom", self.geom),
+            self.dist,
+            label(&self.other_name, "geom", self.other),
+            self.pos[0],
+            self.pos[1],
+            self.pos[2]
+        )
+    }
+}
@@ -0,0 +1,147 @@
+// contact.rs
+use mujoco_rs_sys::no_render;
+use mujoco_rust::Simulation;
+use std::ffi::CStr;
+use std::fmt;
+
+/// One contact of the current step, copied out of `mj_data.contact`
+#[derive(Debug, Clone)]
+pub struct ContactEvent {
+    pub index: usize, // position in mj_data.contact
+    pub geom1: usize,
+    pub geom2: usize,
+    pub geom1_name: String, // empty if the geom has no name
+    pub geom2_name: String,
+    pub body1: usize,
+    pub body2: usize,
+    pub body1_name: String,
+    pub body2_name: String,
+    pub pos: [f64; 3],   // world frame
+    pub frame: [f64; 9], // rows: normal (geom1 -> geom2), tangent1, tangent2
+    pub dist: f64,       // negative means penetration
+    pub force: [f64; 6], // contact frame: normal, 2 friction forces, 3 torques
+}
+
+impl ContactEvent {
+    pub fn normal(&self) -> [f64; 3] {
+        [self.frame[0], self.frame[1], self.frame[2]]
+    }
+
+    /// Contact force acting on geom2, in the world frame
+    pub fn world_force(&self) -> [f64; 3] {
+        let mut f = [0.0; 3];
+        for (row, component) in self.force[..3].iter().enumerate() {
+            for (axis, value) in f.iter_mut().enumerate() {
+                *value += self.frame[3 * row + axis] * component;
+            }
+        }
+        f
+    }
+}
+
+impl fmt::Display for ContactEvent {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let n = self.normal();
+        let force = self.world_force();
+        write!(
+            f,
+            "contact {}: {} ({}) <-> {} ({}) at [{:.3}, {:.3}, {:.3}] normal [{:.2}, {:.2}, {:.2}] dist {:.4} force [{:.3}, {:.3}, {:.3}]",
+            self.index,
+            label(&self.geom1_name, "geom", self.geom1),
+            label(&self.body1_name, "body", self.body1),
+            label(&self.geom2_name, "geom", self.geom2),
+            label(&self.body2_name, "body", self.body2),
+            self.pos[0],
+            self.pos[1],
+            self.pos[2],
+            n[0],
+            n[1],
+            n[2],
+            self.dist,
+            force[0],
+            force[1],
+            force[2],
+        )
+    }
+}
+
+// name if there is one, otherwise "geom 3"
+pub fn label(name: &str, kind: &str, id: usize) -> String {
+    if name.is_empty() {
+        format!("{} {}", kind, id)
+    } else {
+        name.to_string()
+    }
+}
+
+// read a name from mj_model.names at the given address
+fn name_at(simulation: &Simulation, adr: i32) -> String {
+    unsafe {
+        let mj_model = &*simulation.model.ptr();
+        CStr::from_ptr(mj_model.names.add(adr as usize)).to_string_lossy().into_owned()
+    }
+}
+
+pub fn geom_name(simulation: &Simulation, geom: usize) -> String {
+    let adr = unsafe { *(*simulation.model.ptr()).name_geomadr.add(geom) };
+    name_at(simulation, adr)
+}
+
+pub fn body_name(simulation: &Simulation, body: usize) -> String {
+    let adr = unsafe { *(*simulation.model.ptr()).name_bodyadr.add(body) };
+    name_at(simulation, adr)
+}
+
+pub fn geom_body(simulation: &Simulation, geom: usize) -> usize {
+    unsafe { *(*simulation.model.ptr()).geom_bodyid.add(geom) as usize }
+}
+
+/// Iterator over the contacts of the current step
+pub struct Contacts<'a> {
+    simulation: &'a Simulation,
+    index: usize,
+}
+
+/// Iterate over the contacts found by the last `simulation.step()`
+pub fn contacts(simulation: &Simulation) -> Contacts<'_> {
+    Contacts { simulation, index: 0 }
+}
+
+impl Iterator for Contacts<'_> {
+    type Item = ContactEvent;
+
+    fn next(&mut self) -> Option<ContactEvent> {
+        let simulation = self.simulation;
+        let mut force = [0.0; 6];
+        let contact = unsafe {
+            let mj_data = &*simulation.state.ptr(); // ncon is read again in case the sim stepped
+            if self.index >= mj_data.ncon as usize {
+                return None;
+            }
+            no_render::mj_contactForce(simulation.model.ptr(), simulation.state.ptr(), self.index as i32, force.as_mut_ptr());
+            *mj_data.contact.add(self.index)
+        };
+
+        let geom1 = contact.geom1 as usize;
+        let geom2 = contact.geom2 as usize;
+        let body1 = geom_body(simulation, geom1);
+        let body2 = geom_body(simulation, geom2);
+        let event = ContactEvent {
+            index: self.index,
+            geom1,
+            geom2,
+            geom1_name: geom_name(simulation, geom1),
+            geom2_name: geom_name(simulation, geom2),
+            body1,
+            body2,
+            body1_name: body_name(simulation, body1),
+            body2_name: body_name(simulation, body2),
+            pos: contact.pos,
+            frame: contact.frame,
+            dist: contact.dist,
+            force,
+        };
+        self.index += 1;
+        Some(event)
+    }
+}
@@ -0,0 +1,204 @@
+// logger.rs
+use crate::tracker::{CollisionEvent, ContactKind};
+use crate::ui::ContactMarker;
+use serde::{Deserialize, Serialize};
+use std::error::Error;
+use std::fs::File;
+use std::io::{BufRead, BufReader, BufWriter, Write};
+use std::path::Path;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum EventType {
+    Begin,
+    Persist,
+    End,
+    Crash,
+}
+
+/// One line of the log, flat so that it also fits in a CSV row
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ContactRecord {
+    pub time: f64,
+    pub event: EventType,
+    pub kind: ContactKind,
+    pub geom1: usize,
+    pub geom2: usize,
+    pub geom1_name: String,
+    pub geom2_name: String,
+    pub pos_x: f64,
+    pub pos_y: f64,
+    pub pos_z: f64,
+    pub normal_x: f64, // geom1 -> geom2
+    pub normal_y: f64,
+    pub normal_z: f64,
+    pub force: f64, // summed normal force (N)
+    pub speed: f64, // approach speed (m/s)
+}
+
+impl ContactRecord {
+    pub fn from_event(event: &CollisionEvent) -> Self {
+        let (event_type, c) = match event {
+            CollisionEvent::ContactBegin(c) => (EventType::Begin, c),
+            CollisionEvent::ContactPersist(c) => (EventType::Persist, c),
+            CollisionEvent::ContactEnd(c) => (EventType::End, c),
+            CollisionEvent::Crash(c) => (EventType::Crash, c),
+        };
+        ContactRecord {
+            time: c.time,
+            event: event_type,
+            kind: c.kind,
+            geom1: c.geom1,
+            geom2: c.geom2,
+            geom1_name: c.geom1_name.clone(),
+            geom2_name: c.geom2_name.clone(),
+            pos_x: c.pos[0],
+            pos_y: c.pos[1],
+            pos_z: c.pos[2],
+            normal_x: c.normal[0],
+            normal_y: c.normal[1],
+            normal_z: c.normal[2],
+            force: c.force,
+            speed: c.speed,
+        }
+    }
+
+    pub fn pos(&self) -> [f64; 3] {
+        [self.pos_x, self.pos_y, self.pos_z]
+    }
+
+    /// Normal force acting on geom2 as a world frame vector
+    pub fn force_vector(&self) -> [f64; 3] {
+        [self.normal_x * self.force, self.normal_y * self.force, self.normal_z * self.force]
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LogFormat {
+    Csv,
+    JsonLines,
+}
+
+impl LogFormat {
+    /// `.csv` is CSV, anything else JSON Lines
+    pub fn from_path(path: &Path) -> LogFormat {
+        match path.extension().and_then(|e| e.to_str()) {
+            Some("csv") => LogFormat::Csv,
+            _ => LogFormat::JsonLines,
+        }
+    }
+}
+
+enum Writer {
+    Csv(csv::Writer<File>),
+    JsonLines(BufWriter<File>),
+}
+
+/// Writes every tracker event of a run to disk
+pub struct ContactLogger {
+    writer: Writer,
+}
+
+impl ContactLogger {
+    pub fn create(path: &Path) -> Result<Self, Box<dyn Error>> {
+        let file = File::create(path)?;
+        let writer = match LogFormat::from_path(path) {
+            LogFormat::Csv => Writer::Csv(csv::Writer::from_writer(file)),
+            LogFormat::JsonLines => Writer::JsonLines(BufWriter::new(file)),
+        };
+        Ok(ContactLogger { writer })
+    }
+
+    pub fn log(&mut self, events: &[CollisionEvent]) -> Result<(), Box<dyn Error>> {
+        for event in events {
+            let record = ContactRecord::from_event(event);
+            match &mut self.writer {
+                Writer::Csv(w) => w.serialize(&record)?,
+                Writer::JsonLines(w) => {
+                    serde_json::to_writer(&mut *w, &record)?;
+                    w.write_all(b"\n")?;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    pub fn flush(&mut self) -> Result<(), Box<dyn Error>> {
+        match &mut self.writer {
+            Writer::Csv(w) => w.flush()?,
+            Writer::JsonLines(w) => w.flush()?,
+        }
+        Ok(())
+    }
+}
+
+/// Read a log written by `ContactLogger`, the format is picked by extension
+pub fn read_log(path: &Path) -> Result<Vec<ContactRecord>, Box<dyn Error>> {
+    let file = File::open(path)?;
+    let mut records = Vec::new();
+    match LogFormat::from_path(path) {
+        LogFormat::Csv => {
+            for record in csv::Reader::from_reader(file).deserialize() {
+                records.push(record?);
+            }
+        }
+        LogFormat::JsonLines => {
+            for line in BufReader::new(file).lines() {
+                let line = line?;
+                if !line.trim().is_empty() {
+                    records.push(serde_json::from_str(&line)?);
+                }
+            }
+        }
+    }
+    Ok(records)
+}
+
+/// Plays a contact log back as markers for the viewer
+pub struct Replay {
+    records: Vec<ContactRecord>, // sorted by time
+    next: usize,
+    crashes: Vec<ContactMarker>, // stay on screen once reached
+}
+
+impl Replay {
+    pub fn new(mut records: Vec<ContactRecord>) -> Self {
+        records.sort_by(|a, b| a.time.total_cmp(&b.time));
+        Replay { records, next: 0, crashes: Vec::new() }
+    }
+
+    pub fn end_time(&self) -> f64 {
+        self.records.last().map_or(0.0, |r| r.time)
+    }
+
+    /// Markers of the records logged in (time - timestep, time], plus all
+    /// crashes so far. `time` must not go backwards.
+    pub fn markers(&mut self, time: f64, timestep: f64) -> Vec<ContactMarker> {
+        let mut markers = Vec::new();
+        while self.next < self.records.len() && self.records[self.next].time <= time {
+            let record = &self.records[self.next];
+            self.next += 1;
+            if record.time <= time - timestep {
+                continue;
+            }
+            let rgba = match record.event {
+                EventType::Begin => [1.0, 1.0, 0.0, 1.0],
+                EventType::Persist => [0.0, 1.0, 0.0, 1.0],
+                EventType::End => [0.5, 0.5, 0.5, 1.0],
+                EventType::Crash => [1.0, 0.0, 0.0, 1.0],
+            };
+            let marker = ContactMarker {
+                pos: record.pos(),
+                force: record.force_vector(),
+                rgba,
+            };
+            if record.event == EventType::Crash {
+                self.crashes.push(marker);
+            } else {
+                markers.push(marker);
+            }
+        }
+        markers.extend(self.crashes.iter().cloned());
+        markers
+    }
+}
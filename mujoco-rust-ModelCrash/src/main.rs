@@ -1,12 +1,78 @@
 // main.rs
 mod ui;
+mod contact;
+mod tracker;
+mod severity;
+mod logger;
+mod collision;
+mod snapshot;
+mod recording;
 
+use mujoco_compose::actors;
+use std::path::PathBuf;
+
+// usage: mujoco-rust-ModelCrash [--log <file.csv|file.jsonl>] [--replay <file.csv|file.jsonl>]
+//                               [--disable <geom>]... [--watch <geom1> <geom2>]
+//                               [--restore <snapshot>] [--crash-snapshot <snapshot>]
+//                               [--record <file>] [--play <file>] [--verify <file>]
+//                               [--scene <file>]
 fn main() {
+    let mut log_path: Option<PathBuf> = None;
+    let mut replay_path: Option<PathBuf> = None;
+    let mut restore_path: Option<PathBuf> = None;
+    let mut crash_snapshot_path: Option<PathBuf> = None;
+    let mut record_path: Option<PathBuf> = None;
+    let mut play_path: Option<PathBuf> = None;
+    let mut verify_path: Option<PathBuf> = None;
+    let mut disabled: Vec<String> = Vec::new();
+    let mut watched: Option<(String, String)> = None;
+    let mut scene = "../x2/scene.xml".to_string();
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--log" => log_path = args.next().map(PathBuf::from),
+            "--replay" => replay_path = args.next().map(PathBuf::from),
+            "--disable" => disabled.extend(args.next()),
+            "--watch" => watched = args.next().zip(args.next()),
+            "--restore" => restore_path = args.next().map(PathBuf::from),
+            "--crash-snapshot" => crash_snapshot_path = args.next().map(PathBuf::from),
+            "--record" => record_path = args.next().map(PathBuf::from),
+            "--play" => play_path = args.next().map(PathBuf::from),
+            "--verify" => verify_path = args.next().map(PathBuf::from),
+            "--scene" => scene = args.next().unwrap_or(scene),
+            _ => eprintln!("unknown argument: {}", arg),
+        }
+    }
 
     // init model
-    let model = mujoco_rust::Model::from_xml("../x2/scene.xml".to_string()).unwrap();
+    let model = mujoco_rust::Model::from_xml(scene).unwrap();
     let simulation = mujoco_rust::Simulation::new(model.clone());
 
+    // geoms that should not collide in this run, e.g. to fly through a wall
+    for name in disabled.iter() {
+        match collision::geom_id(&simulation, name) {
+            Some(geom) => collision::set_collision_filter(&simulation, geom, 0, 0),
+            None => eprintln!("no geom named {}", name),
+        }
+    }
+    println!("collision pairs:");
+    for pair in collision::collision_pairs(&simulation) {
+        println!("  {}", pair);
+    }
+    let watched = watched.map(|(a, b)| (collision::geom_id(&simulation, &a).unwrap(), collision::geom_id(&simulation, &b).unwrap()));
+    let distance = collision::GeomDistance::new(&simulation);
+
+    // replay a recording headlessly & compare qpos with the recorded one
+    if let Some(path) = verify_path {
+        let recording = recording::Recording::load(&path).unwrap();
+        let steps = recording.steps();
+        match recording::verify(recording, &simulation) {
+            Ok(_) => println!("{}: {} steps replayed bit-exactly", path.display(), steps),
+            Err(e) => println!("{}: {}", path.display(), e),
+        }
+        return;
+    }
+
     // init ctrl vector 
     let actuator_num = unsafe { (*simulation.model.ptr()).nu };
     let mut ctrl: Vec<f64> = vec![0.0; actuator_num as usize]; 
@@ -14,7 +80,49 @@ fn main() {
     // init glfw
     let mut glfw = glfw::init(glfw::FAIL_ON_ERRORS).unwrap();
     let mut ui_state = ui::ui_init(&mut glfw, &simulation, [0x7FFFFFFF].as_ref()); // 3rd-person perspective 
+
+    // draw a recorded run instead of simulating
+    if let Some(path) = replay_path {
+        replay_log(&simulation, &mut glfw, &mut ui_state, &path);
+        ui::free_glfw(&mut ui_state);
+        return;
+    }
+    // replay a recorded run in the viewer
+    if let Some(path) = play_path {
+        play_recording(&simulation, &mut glfw, &mut ui_state, &path);
+        ui::free_glfw(&mut ui_state);
+        return;
+    }
+
+    // continue a run from a snapshot, e.g. one second before a crash
+    if let Some(path) = restore_path.as_ref() {
+        let snapshot = snapshot::Snapshot::load(path).unwrap();
+        snapshot.restore(&simulation).unwrap();
+        println!("restored {} at {:.2}s", path.display(), snapshot.time);
+    }
+    // steps per simulated second, for the things done once a second
+    let second = unsafe { ((1.0 / (*simulation.model.ptr()).opt.timestep).round() as i64).max(1) };
+    // snapshot of the last whole second, written at the first crash
+    let mut last_snapshot = snapshot::Snapshot::capture(&simulation);
+    let mut crash_saved = false;
+    // ctrl of every step & a checkpoint every second
+    let mut recorder = record_path.as_ref().map(|_| recording::Recorder::new(&simulation, second as usize));
+
+    // write every contact event of the run
+    let mut contact_log = log_path.map(|path| logger::ContactLogger::create(&path).unwrap());
     
+    // collision tracker with the default crash thresholds
+    let mut tracker = tracker::CollisionTracker::new(tracker::CrashThresholds::default());
+    // impact severity of the drone, per collision geom
+    let mut impacts = severity::ImpactMonitor::new(&simulation, "x2");
+    // warn when the drone gets within 0.3m of an obstacle
+    let mut proximity = collision::ProximityMonitor::new(&simulation, "x2", 0.3);
+    // moving vehicles of the street scene, none in the other scenes
+    let traffic = actors::street_traffic(&simulation);
+    for actor in traffic.actors.iter() {
+        println!("{} moves along a {:.0}m route at {}m/s", actor.name, actor.route.total_length(), actor.route.speed);
+    }
+
     // sim running until the window closes
     while !ui_state.window.should_close() {
 
@@ -24,30 +132,149 @@ fn main() {
         // fixed ctrl array
         ctrl[..3].fill(3.7);
         ctrl[3] = 4.0;
-        simulation.control(&ctrl);
+        traffic.update(&simulation);
 
         // sim forward a step
-        simulation.step();
-
-        // get contact data in mj_data
-        unsafe {
-            let mj_data = &*simulation.state.ptr(); // mj_data needs to be updated in real time
-            let ncon = mj_data.ncon as usize;
-            for i in 0..ncon {
-                let contact = &*mj_data.contact.add(i);
-                let pos = contact.pos;
-                println!(
-                    "contact {} at [{}, {}, {}]",
-                    i, pos[0], pos[1], pos[2]
-                );
+        match recorder.as_mut() {
+            Some(recorder) => recorder.step(&simulation, &ctrl),
+            None => {
+                simulation.control(&ctrl);
+                simulation.step();
+            }
+        }
+
+        // print new, ended contacts & crashes, resting contacts are not repeated
+        let events = tracker.update(&simulation);
+        if let Some(path) = crash_snapshot_path.as_ref() {
+            let crashed = events.iter().any(|e| matches!(e, tracker::CollisionEvent::Crash(_)));
+            if crashed && !crash_saved {
+                last_snapshot.save(path).unwrap();
+                println!("snapshot of {:.2}s written to {}, rerun with --restore {}", last_snapshot.time, path.display(), path.display());
+                crash_saved = true;
+            }
+            let step = unsafe { ((*simulation.state.ptr()).time / (*simulation.model.ptr()).opt.timestep).round() as i64 };
+            if step % second == 0 {
+                last_snapshot = snapshot::Snapshot::capture(&simulation);
+            }
+        }
+        for event in events.iter() {
+            match event {
+                tracker::CollisionEvent::ContactPersist(_) => {}
+                _ => println!("{}", event),
+            }
+        }
+        for report in impacts.update(&simulation, &events) {
+            println!("{}", report);
+        }
+        if let Some(contact_log) = contact_log.as_mut() {
+            contact_log.log(&events).unwrap();
+        }
+        for warning in proximity.update(&simulation) {
+            println!("proximity warning: {}", warning);
+        }
+        // distance between the watched geoms, once per simulated second
+        if let Some((geom1, geom2)) = watched {
+            let step = unsafe { ((*simulation.state.ptr()).time / (*simulation.model.ptr()).opt.timestep).round() as i64 };
+            if step % second == 0 {
+                match distance.query(&simulation, geom1, geom2, 10.0) {
+                    Some(dist) => println!("distance: {:.3}m", dist),
+                    None => println!("distance: > 10m"),
+                }
             }
         }
         // Get events in real time
         glfw.poll_events();
         glfw::flush_messages(&ui_state.events);
     }
+    // damage attributed to each collision geom of the drone, with the
+    // contacts that have not ended yet
+    for report in impacts.finish(&simulation) {
+        println!("{}", report);
+    }
+    impacts.print_damage();
+    if let Some(contact_log) = contact_log.as_mut() {
+        contact_log.flush().unwrap();
+    }
+    if let (Some(recorder), Some(path)) = (recorder, record_path) {
+        let recording = recorder.finish();
+        recording.save(&path).unwrap();
+        println!("{} steps recorded to {}", recording.steps(), path.display());
+    }
+
     // free glfw resources
     ui::free_glfw(&mut ui_state);
 
 }
 
+// draw the contact points & force arrows of a log at the logged times
+fn replay_log(simulation: &mujoco_rust::Simulation, glfw: &mut glfw::Glfw, ui_state: &mut ui::UIState, path: &std::path::Path) {
+    let records = logger::read_log(path).unwrap();
+    println!("replaying {} contact events from {}", records.len(), path.display());
+    let mut replay = logger::Replay::new(records);
+
+    let timestep = unsafe { (*simulation.model.ptr()).opt.timestep };
+    let mut time = 0.0;
+    unsafe {
+        mujoco_rs_sys::no_render::mj_forward(simulation.model.ptr(), simulation.state.ptr());
+    }
+
+    // keep the window open after the end to look at the crashes
+    while !ui_state.window.should_close() {
+        let markers = replay.markers(time, timestep);
+        ui::update_scene_with_markers(simulation, ui_state, &markers);
+        if time <= replay.end_time() {
+            time += timestep;
+        }
+
+        glfw.poll_events();
+        glfw::flush_messages(&ui_state.events);
+    }
+}
+
+// replay a recording in the viewer: Space pauses, Left/Right step while
+// paused, Comma/Period jump one second back/forward
+fn play_recording(simulation: &mujoco_rust::Simulation, glfw: &mut glfw::Glfw, ui_state: &mut ui::UIState, path: &std::path::Path) {
+    let recording = recording::Recording::load(path).unwrap();
+    println!("playing {} steps from {}", recording.steps(), path.display());
+    let second = ((1.0 / recording.timestep).round() as usize).max(1);
+    let mut player = recording::Player::new(recording);
+    player.seek(simulation, 0).unwrap();
+    let mut paused = false;
+
+    while !ui_state.window.should_close() {
+        ui::update_scene(simulation, ui_state);
+
+        if !paused {
+            match player.step_forward(simulation) {
+                Ok(true) => {}
+                Ok(false) => paused = true, // end of the recording
+                Err(e) => {
+                    println!("{}", e);
+                    paused = true;
+                }
+            }
+        }
+
+        glfw.poll_events();
+        for (_, event) in glfw::flush_messages(&ui_state.events) {
+            let glfw::WindowEvent::Key(key, _, glfw::Action::Press | glfw::Action::Repeat, _) = event else {
+                continue;
+            };
+            let moved = match key {
+                glfw::Key::Space => {
+                    paused = !paused;
+                    continue;
+                }
+                glfw::Key::Right if paused => player.step_forward(simulation).map(|_| ()).map_err(|e| e.into()),
+                glfw::Key::Left if paused => player.step_back(simulation),
+                glfw::Key::Period => player.seek(simulation, player.step() + second),
+                glfw::Key::Comma => player.seek(simulation, player.step().saturating_sub(second)),
+                _ => continue,
+            };
+            if let Err(e) = moved {
+                println!("{}", e);
+            }
+            println!("[{:.2}s] step {}", player.time(), player.step());
+        }
+    }
+}
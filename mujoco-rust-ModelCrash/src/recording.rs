@@ -0,0 +1,218 @@
+// recording.rs
+use crate::snapshot::{Snapshot, SnapshotFormat};
+use mujoco_rust::Simulation;
+use serde::{Deserialize, Serialize};
+use std::error::Error;
+use std::fmt;
+use std::fs::File;
+use std::io::{BufReader, BufWriter};
+use std::path::Path;
+
+/// Full state at the start of a step, replay seeks from the nearest one
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Checkpoint {
+    pub step: usize,
+    pub snapshot: Snapshot,
+}
+
+/// A whole run: the ctrl and mocap poses of every step plus periodic
+/// checkpoints, and the resulting qpos of every step to check a replay
+/// against
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Recording {
+    pub timestep: f64,
+    pub checkpoint_interval: usize, // steps
+    pub ctrl: Vec<Vec<f64>>,        // applied before step i
+    #[serde(default)]
+    pub mocap: Vec<Vec<f64>>, // mocap_pos & mocap_quat before step i, empty without mocap bodies
+    pub qpos: Vec<Vec<f64>>,        // after step i
+    pub checkpoints: Vec<Checkpoint>,
+}
+
+impl Recording {
+    pub fn steps(&self) -> usize {
+        self.ctrl.len()
+    }
+
+    /// Write as JSON or binary depending on the extension, like snapshots
+    pub fn save(&self, path: &Path) -> Result<(), Box<dyn Error>> {
+        let writer = BufWriter::new(File::create(path)?);
+        match SnapshotFormat::from_path(path) {
+            SnapshotFormat::Json => serde_json::to_writer(writer, self)?,
+            SnapshotFormat::Binary => bincode::serialize_into(writer, self)?,
+        }
+        Ok(())
+    }
+
+    pub fn load(path: &Path) -> Result<Self, Box<dyn Error>> {
+        let reader = BufReader::new(File::open(path)?);
+        let recording = match SnapshotFormat::from_path(path) {
+            SnapshotFormat::Json => serde_json::from_reader(reader)?,
+            SnapshotFormat::Binary => bincode::deserialize_from(reader)?,
+        };
+        Ok(recording)
+    }
+}
+
+/// Records a run, use `recorder.step` in place of `simulation.control` +
+/// `simulation.step`
+pub struct Recorder {
+    recording: Recording,
+}
+
+impl Recorder {
+    /// Starts from the current state of the simulation
+    pub fn new(simulation: &Simulation, checkpoint_interval: usize) -> Self {
+        let timestep = unsafe { (*simulation.model.ptr()).opt.timestep };
+        Recorder {
+            recording: Recording {
+                timestep,
+                checkpoint_interval: checkpoint_interval.max(1),
+                ctrl: Vec::new(),
+                mocap: Vec::new(),
+                qpos: Vec::new(),
+                checkpoints: vec![Checkpoint { step: 0, snapshot: Snapshot::capture(simulation) }],
+            },
+        }
+    }
+
+    pub fn step(&mut self, simulation: &Simulation, ctrl: &[f64]) {
+        let step = self.recording.ctrl.len();
+        if step > 0 && step % self.recording.checkpoint_interval == 0 {
+            self.recording.checkpoints.push(Checkpoint { step, snapshot: Snapshot::capture(simulation) });
+        }
+        // moved by scripted actors, not by the physics
+        let mocap = mocap(simulation);
+        if !mocap.is_empty() {
+            self.recording.mocap.push(mocap);
+        }
+        simulation.control(ctrl);
+        simulation.step();
+        self.recording.ctrl.push(ctrl.to_vec());
+        self.recording.qpos.push(qpos(simulation));
+    }
+
+    pub fn finish(self) -> Recording {
+        self.recording
+    }
+}
+
+/// First step whose replayed qpos is not bit-equal to the recorded one
+#[derive(Debug, Clone)]
+pub struct Divergence {
+    pub step: usize,
+    pub max_error: f64,
+}
+
+impl fmt::Display for Divergence {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "replay diverged at step {}, max qpos error {:e}", self.step, self.max_error)
+    }
+}
+
+impl Error for Divergence {}
+
+/// Replays a recording on a simulation of the same model, forward step by
+/// step or jumping to any step through the checkpoints
+pub struct Player {
+    pub recording: Recording,
+    step: usize, // steps applied so far
+}
+
+impl Player {
+    pub fn new(recording: Recording) -> Self {
+        Player { recording, step: 0 }
+    }
+
+    pub fn step(&self) -> usize {
+        self.step
+    }
+
+    pub fn time(&self) -> f64 {
+        self.step as f64 * self.recording.timestep
+    }
+
+    /// Restore the last checkpoint at or before `step`, then replay the
+    /// ctrl up to it
+    pub fn seek(&mut self, simulation: &Simulation, step: usize) -> Result<(), Box<dyn Error>> {
+        let step = step.min(self.recording.steps());
+        let checkpoint = self
+            .recording
+            .checkpoints
+            .iter()
+            .rev()
+            .find(|c| c.step <= step)
+            .ok_or("recording has no checkpoint")?;
+        checkpoint.snapshot.restore(simulation)?;
+        self.step = checkpoint.step;
+        while self.step < step {
+            self.step_forward(simulation)?;
+        }
+        Ok(())
+    }
+
+    /// Apply the recorded ctrl of the next step, false at the end. The qpos
+    /// after the step must be bit-equal to the recorded one; the step is
+    /// counted either way, the simulation has moved on.
+    pub fn step_forward(&mut self, simulation: &Simulation) -> Result<bool, Divergence> {
+        if self.step >= self.recording.steps() {
+            return Ok(false);
+        }
+        if let Some(mocap) = self.recording.mocap.get(self.step) {
+            set_mocap(simulation, mocap);
+        }
+        simulation.control(&self.recording.ctrl[self.step]);
+        simulation.step();
+
+        let step = self.step;
+        self.step += 1;
+
+        let expected = &self.recording.qpos[step];
+        let replayed = qpos(simulation);
+        if replayed != *expected {
+            let max_error = replayed.iter().zip(expected.iter()).map(|(a, b)| (a - b).abs()).fold(0.0, f64::max);
+            return Err(Divergence { step, max_error });
+        }
+        Ok(true)
+    }
+
+    pub fn step_back(&mut self, simulation: &Simulation) -> Result<(), Box<dyn Error>> {
+        self.seek(simulation, self.step.saturating_sub(1))
+    }
+}
+
+/// Replay the whole recording headlessly from its first checkpoint
+pub fn verify(recording: Recording, simulation: &Simulation) -> Result<usize, Box<dyn Error>> {
+    let mut player = Player::new(recording);
+    player.seek(simulation, 0)?;
+    while player.step_forward(simulation)? {}
+    Ok(player.step())
+}
+
+fn qpos(simulation: &Simulation) -> Vec<f64> {
+    unsafe {
+        let nq = (*simulation.model.ptr()).nq as usize;
+        std::slice::from_raw_parts((*simulation.state.ptr()).qpos, nq).to_vec()
+    }
+}
+
+fn mocap(simulation: &Simulation) -> Vec<f64> {
+    unsafe {
+        let nmocap = (*simulation.model.ptr()).nmocap as usize;
+        let mj_data = &*simulation.state.ptr();
+        let mut mocap = std::slice::from_raw_parts(mj_data.mocap_pos, 3 * nmocap).to_vec();
+        mocap.extend_from_slice(std::slice::from_raw_parts(mj_data.mocap_quat, 4 * nmocap));
+        mocap
+    }
+}
+
+fn set_mocap(simulation: &Simulation, mocap: &[f64]) {
+    unsafe {
+        let nmocap = (*simulation.model.ptr()).nmocap as usize;
+        let mj_data = &*simulation.state.ptr();
+        if mocap.len() == 7 * nmocap {
+            std::slice::from_raw_parts_mut(mj_data.mocap_pos, 3 * nmocap).copy_from_slice(&mocap[..3 * nmocap]);
+            std::slice::from_raw_parts_mut(mj_data.mocap_quat, 4 * nmocap).copy_from_slice(&mocap[3 * nmocap..]);
+        }
+    }
+}
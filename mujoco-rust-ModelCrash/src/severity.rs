@@ -0,0 +1,199 @@
+// severity.rs
+use crate::contact::{self, label};
+use crate::tracker::{CollisionEvent, ContactKind, TrackedContact};
+use mujoco_rust::model::ObjType;
+use mujoco_rust::Simulation;
+use std::collections::BTreeMap;
+use std::fmt;
+
+/// How hard one geom of the monitored body hit something, from the first
+/// to the last step of the contact
+#[derive(Debug, Clone)]
+pub struct ImpactReport {
+    pub geom: usize, // geom of the monitored body
+    pub geom_name: String,
+    pub other_name: String, // what it hit
+    pub kind: ContactKind,
+    pub begin_time: f64,
+    pub duration: f64,
+    pub peak_force: f64,            // max summed normal force (N)
+    pub impulse: f64,               // normal force integrated over the contact (N·s)
+    pub approach_speed: f64,        // along the contact normal (m/s)
+    pub pre_impact_velocity: [f64; 3], // linear velocity of the body one step before the contact (m/s)
+}
+
+impl ImpactReport {
+    pub fn pre_impact_speed(&self) -> f64 {
+        let v = self.pre_impact_velocity;
+        (v[0] * v[0] + v[1] * v[1] + v[2] * v[2]).sqrt()
+    }
+}
+
+impl fmt::Display for ImpactReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "[{:.2}s] impact {:?} {} -> {} for {:.2}s: peak {:.2}N impulse {:.3}N·s approach {:.2}m/s pre-impact {:.2}m/s",
+            self.begin_time,
+            self.kind,
+            label(&self.geom_name, "geom", self.geom),
+            self.other_name,
+            self.duration,
+            self.peak_force,
+            self.impulse,
+            self.approach_speed,
+            self.pre_impact_speed()
+        )
+    }
+}
+
+/// Impacts accumulated on one collision geom, e.g. a rotor
+#[derive(Debug, Clone, Default)]
+pub struct GeomDamage {
+    pub name: String,
+    pub impacts: usize,
+    pub total_impulse: f64,
+    pub peak_force: f64,
+    pub max_approach_speed: f64,
+}
+
+/// Turns the tracker events of one body (e.g. `x2`) into impact reports
+pub struct ImpactMonitor {
+    pub body: usize,
+    timestep: f64,
+    prev_velocity: [f64; 6],
+    active: BTreeMap<(usize, usize), ImpactReport>,
+    pub damage: BTreeMap<usize, GeomDamage>, // by geom id
+}
+
+impl ImpactMonitor {
+    pub fn new(simulation: &Simulation, body_name: &str) -> Self {
+        let body = simulation.model.name_to_id(ObjType::BODY, body_name).unwrap() as usize;
+        let timestep = unsafe { (*simulation.model.ptr()).opt.timestep };
+        ImpactMonitor {
+            body,
+            timestep,
+            prev_velocity: body_velocity(simulation, body),
+            active: BTreeMap::new(),
+            damage: BTreeMap::new(),
+        }
+    }
+
+    /// Feed the events of `CollisionTracker::update`, returns the impacts
+    /// that ended in this step
+    pub fn update(&mut self, simulation: &Simulation, events: &[CollisionEvent]) -> Vec<ImpactReport> {
+        let mut finished = Vec::new();
+        for event in events {
+            match event {
+                CollisionEvent::ContactBegin(c) => {
+                    if let Some(report) = self.begin(simulation, c) {
+                        self.active.insert(key(c), report);
+                    }
+                }
+                CollisionEvent::ContactPersist(c) => {
+                    if let Some(report) = self.active.get_mut(&key(c)) {
+                        report.peak_force = report.peak_force.max(c.force);
+                        report.impulse += c.force * self.timestep;
+                        report.approach_speed = report.approach_speed.max(c.speed);
+                    }
+                }
+                CollisionEvent::ContactEnd(c) => {
+                    if let Some(mut report) = self.active.remove(&key(c)) {
+                        report.duration = c.duration();
+                        self.add_damage(&report);
+                        finished.push(report);
+                    }
+                }
+                CollisionEvent::Crash(_) => {}
+            }
+        }
+        self.prev_velocity = body_velocity(simulation, self.body);
+        finished
+    }
+
+    // start a report if one geom of the contact belongs to the monitored body
+    fn begin(&self, simulation: &Simulation, c: &TrackedContact) -> Option<ImpactReport> {
+        let (geom, geom_name, other, other_name) = if contact::geom_body(simulation, c.geom1) == self.body {
+            (c.geom1, &c.geom1_name, c.geom2, &c.geom2_name)
+        } else if contact::geom_body(simulation, c.geom2) == self.body {
+            (c.geom2, &c.geom2_name, c.geom1, &c.geom1_name)
+        } else {
+            return None;
+        };
+
+        Some(ImpactReport {
+            geom,
+            geom_name: geom_name.clone(),
+            other_name: label(other_name, "geom", other),
+            kind: c.kind,
+            begin_time: c.begin_time,
+            duration: 0.0,
+            peak_force: c.force,
+            impulse: c.force * self.timestep,
+            approach_speed: c.speed,
+            pre_impact_velocity: [self.prev_velocity[3], self.prev_velocity[4], self.prev_velocity[5]],
+        })
+    }
+
+    fn add_damage(&mut self, report: &ImpactReport) {
+        let damage = self.damage.entry(report.geom).or_default();
+        damage.name = label(&report.geom_name, "geom", report.geom);
+        damage.impacts += 1;
+        damage.total_impulse += report.impulse;
+        damage.peak_force = damage.peak_force.max(report.peak_force);
+        damage.max_approach_speed = damage.max_approach_speed.max(report.approach_speed);
+    }
+
+    /// End the impacts still going on, e.g. the drone resting on the floor
+    /// or against a wall after a crash, and count their damage; call when
+    /// the run is over, before `print_damage`
+    pub fn finish(&mut self, simulation: &Simulation) -> Vec<ImpactReport> {
+        let time = unsafe { (*simulation.state.ptr()).time };
+        let mut finished = Vec::new();
+        for (_, mut report) in std::mem::take(&mut self.active) {
+            report.duration = time - report.begin_time;
+            self.add_damage(&report);
+            finished.push(report);
+        }
+        finished
+    }
+
+    pub fn print_damage(&self) {
+        println!("{:<12} {:>8} {:>14} {:>12} {:>14}", "geom", "impacts", "impulse(N·s)", "peak(N)", "approach(m/s)");
+        for damage in self.damage.values() {
+            println!(
+                "{:<12} {:>8} {:>14.3} {:>12.2} {:>14.2}",
+                damage.name, damage.impacts, damage.total_impulse, damage.peak_force, damage.max_approach_speed
+            );
+        }
+    }
+}
+
+fn key(c: &TrackedContact) -> (usize, usize) {
+    (c.geom1.min(c.geom2), c.geom1.max(c.geom2))
+}
+
+/// Velocity [ang, lin] of a body at its origin in the world frame, from cvel
+///
+/// cvel is expressed at the center of mass of the whole tree, so the linear
+/// part is moved to the body origin: v = v_com + w x (xpos - subtree_com).
+pub fn body_velocity(simulation: &Simulation, body: usize) -> [f64; 6] {
+    unsafe {
+        let mj_model = &*simulation.model.ptr();
+        let mj_data = &*simulation.state.ptr();
+        let root = *mj_model.body_rootid.add(body) as usize;
+        let cvel = std::slice::from_raw_parts(mj_data.cvel.add(6 * body), 6);
+        let xpos = std::slice::from_raw_parts(mj_data.xpos.add(3 * body), 3);
+        let com = std::slice::from_raw_parts(mj_data.subtree_com.add(3 * root), 3);
+
+        let r = [xpos[0] - com[0], xpos[1] - com[1], xpos[2] - com[2]];
+        [
+            cvel[0],
+            cvel[1],
+            cvel[2],
+            cvel[3] + cvel[1] * r[2] - cvel[2] * r[1],
+            cvel[4] + cvel[2] * r[0] - cvel[0] * r[2],
+            cvel[5] + cvel[0] * r[1] - cvel[1] * r[0],
+        ]
+    }
+}
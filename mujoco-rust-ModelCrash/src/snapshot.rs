@@ -0,0 +1,153 @@
+// snapshot.rs
+use mujoco_rs_sys::no_render;
+use mujoco_rust::Simulation;
+use serde::{Deserialize, Serialize};
+use std::error::Error;
+use std::fs::File;
+use std::io::{BufReader, BufWriter};
+use std::path::Path;
+
+/// Everything in mj_data that the next `simulation.step()` depends on
+///
+/// MuJoCo 2.3.5 keeps no RNG in mj_data (sensor `noise` is not applied by
+/// the engine), so restoring a snapshot and applying the same ctrl gives the
+/// same steps. State kept outside of MuJoCo, e.g. the previous velocities of
+/// `CollisionTracker`, is not part of it.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Snapshot {
+    pub time: f64,
+    pub qpos: Vec<f64>,
+    pub qvel: Vec<f64>,
+    pub act: Vec<f64>,
+    pub ctrl: Vec<f64>,
+    pub qacc_warmstart: Vec<f64>, // solver warm start, the steps differ slightly without it
+    pub qfrc_applied: Vec<f64>,
+    pub xfrc_applied: Vec<f64>,
+    pub mocap_pos: Vec<f64>,
+    pub mocap_quat: Vec<f64>,
+    pub userdata: Vec<f64>,
+    pub sensordata: Vec<f64>, // readings of the last step
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SnapshotFormat {
+    Binary,
+    Json,
+}
+
+impl SnapshotFormat {
+    /// `.json` is JSON, anything else binary
+    pub fn from_path(path: &Path) -> SnapshotFormat {
+        match path.extension().and_then(|e| e.to_str()) {
+            Some("json") => SnapshotFormat::Json,
+            _ => SnapshotFormat::Binary,
+        }
+    }
+}
+
+impl Snapshot {
+    pub fn capture(simulation: &Simulation) -> Self {
+        unsafe {
+            let mj_model = &*simulation.model.ptr();
+            let mj_data = &*simulation.state.ptr();
+            let nv = mj_model.nv as usize;
+            let nmocap = mj_model.nmocap as usize;
+            Snapshot {
+                time: mj_data.time,
+                qpos: read(mj_data.qpos, mj_model.nq as usize),
+                qvel: read(mj_data.qvel, nv),
+                act: read(mj_data.act, mj_model.na as usize),
+                ctrl: read(mj_data.ctrl, mj_model.nu as usize),
+                qacc_warmstart: read(mj_data.qacc_warmstart, nv),
+                qfrc_applied: read(mj_data.qfrc_applied, nv),
+                xfrc_applied: read(mj_data.xfrc_applied, 6 * mj_model.nbody as usize),
+                mocap_pos: read(mj_data.mocap_pos, 3 * nmocap),
+                mocap_quat: read(mj_data.mocap_quat, 4 * nmocap),
+                userdata: read(mj_data.userdata, mj_model.nuserdata as usize),
+                sensordata: read(mj_data.sensordata, mj_model.nsensordata as usize),
+            }
+        }
+    }
+
+    /// Put the snapshot back into mj_data, the model must be the one it was
+    /// captured from
+    pub fn restore(&self, simulation: &Simulation) -> Result<(), Box<dyn Error>> {
+        unsafe {
+            let m = simulation.model.ptr();
+            let d = simulation.state.ptr();
+            let mj_model = &*m;
+            let mj_data = &mut *d;
+            let nv = mj_model.nv as usize;
+            let nmocap = mj_model.nmocap as usize;
+            let sizes = [
+                ("qpos", self.qpos.len(), mj_model.nq as usize),
+                ("qvel", self.qvel.len(), nv),
+                ("act", self.act.len(), mj_model.na as usize),
+                ("ctrl", self.ctrl.len(), mj_model.nu as usize),
+                ("qacc_warmstart", self.qacc_warmstart.len(), nv),
+                ("qfrc_applied", self.qfrc_applied.len(), nv),
+                ("xfrc_applied", self.xfrc_applied.len(), 6 * mj_model.nbody as usize),
+                ("mocap_pos", self.mocap_pos.len(), 3 * nmocap),
+                ("mocap_quat", self.mocap_quat.len(), 4 * nmocap),
+                ("userdata", self.userdata.len(), mj_model.nuserdata as usize),
+                ("sensordata", self.sensordata.len(), mj_model.nsensordata as usize),
+            ];
+            for (name, len, expected) in sizes {
+                if len != expected {
+                    return Err(format!("snapshot has {} {} values, the model {}", len, name, expected).into());
+                }
+            }
+
+            mj_data.time = self.time;
+            copy(&self.qpos, mj_data.qpos);
+            copy(&self.qvel, mj_data.qvel);
+            copy(&self.act, mj_data.act);
+            copy(&self.ctrl, mj_data.ctrl);
+            copy(&self.qacc_warmstart, mj_data.qacc_warmstart);
+            copy(&self.qfrc_applied, mj_data.qfrc_applied);
+            copy(&self.xfrc_applied, mj_data.xfrc_applied);
+            copy(&self.mocap_pos, mj_data.mocap_pos);
+            copy(&self.mocap_quat, mj_data.mocap_quat);
+            copy(&self.userdata, mj_data.userdata);
+
+            // positions, contacts etc. for the viewer & the tracker, then the
+            // readings as they were
+            no_render::mj_forward(m, d);
+            copy(&self.sensordata, mj_data.sensordata);
+        }
+        Ok(())
+    }
+
+    /// Write as JSON or binary depending on the extension
+    pub fn save(&self, path: &Path) -> Result<(), Box<dyn Error>> {
+        let writer = BufWriter::new(File::create(path)?);
+        match SnapshotFormat::from_path(path) {
+            SnapshotFormat::Json => serde_json::to_writer_pretty(writer, self)?,
+            SnapshotFormat::Binary => bincode::serialize_into(writer, self)?,
+        }
+        Ok(())
+    }
+
+    pub fn load(path: &Path) -> Result<Self, Box<dyn Error>> {
+        let reader = BufReader::new(File::open(path)?);
+        let snapshot = match SnapshotFormat::from_path(path) {
+            SnapshotFormat::Json => serde_json::from_reader(reader)?,
+            SnapshotFormat::Binary => bincode::deserialize_from(reader)?,
+        };
+        Ok(snapshot)
+    }
+}
+
+// arrays of size 0 (e.g. act without activations) may be null
+unsafe fn read(p: *const f64, n: usize) -> Vec<f64> {
+    if n == 0 {
+        return Vec::new();
+    }
+    unsafe { std::slice::from_raw_parts(p, n).to_vec() }
+}
+
+unsafe fn copy(values: &[f64], p: *mut f64) {
+    if !values.is_empty() {
+        unsafe { std::ptr::copy_nonoverlapping(values.as_ptr(), p, values.len()) }
+    }
+}
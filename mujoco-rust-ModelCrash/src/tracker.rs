@@ -0,0 +1,266 @@
+// tracker.rs
+use crate::contact::{self, label, ContactEvent};
+use mujoco_rs_sys::no_render;
+use mujoco_rust::Simulation;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fmt;
+
+/// What the geoms of a contact are
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ContactKind {
+    Ground,        // one geom is a plane of the world body
+    Obstacle,      // any other pair of different bodies
+    SelfCollision, // two bodies of the same kinematic tree
+}
+
+/// A crash is raised when either value is exceeded by a contact
+#[derive(Debug, Clone, Copy)]
+pub struct CrashThresholds {
+    pub force: f64, // summed normal force of a geom pair (N)
+    pub speed: f64, // approach speed along the normal before the impact (m/s)
+}
+
+impl Default for CrashThresholds {
+    fn default() -> Self {
+        // the x2 weighs ~13N resting on its landing boxes
+        CrashThresholds { force: 50.0, speed: 1.5 }
+    }
+}
+
+/// All contacts between one pair of geoms, merged over the steps they touch
+#[derive(Debug, Clone)]
+pub struct TrackedContact {
+    pub geom1: usize,
+    pub geom2: usize,
+    pub geom1_name: String,
+    pub geom2_name: String,
+    pub kind: ContactKind,
+    pub pos: [f64; 3],    // mean position of the contact points
+    pub normal: [f64; 3], // normal of the deepest contact point, geom1 -> geom2
+    pub dist: f64,        // deepest penetration (negative)
+    pub force: f64,       // summed normal force (N)
+    pub speed: f64,       // approach speed along the normal (m/s)
+    pub begin_time: f64,
+    pub time: f64,        // time of the last update
+    pub crashed: bool,    // a crash was already raised for this contact
+}
+
+impl TrackedContact {
+    pub fn duration(&self) -> f64 {
+        self.time - self.begin_time
+    }
+
+    fn pair_label(&self) -> String {
+        format!(
+            "{} <-> {}",
+            label(&self.geom1_name, "geom", self.geom1),
+            label(&self.geom2_name, "geom", self.geom2)
+        )
+    }
+}
+
+#[derive(Debug, Clone)]
+pub enum CollisionEvent {
+    ContactBegin(TrackedContact),
+    ContactPersist(TrackedContact),
+    ContactEnd(TrackedContact),
+    Crash(TrackedContact),
+}
+
+impl fmt::Display for CollisionEvent {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CollisionEvent::ContactBegin(c) => write!(
+                f,
+                "[{:.2}s] begin   {:?} {} at [{:.3}, {:.3}, {:.3}] speed {:.2}m/s",
+                c.time, c.kind, c.pair_label(), c.pos[0], c.pos[1], c.pos[2], c.speed
+            ),
+            CollisionEvent::ContactPersist(c) => write!(
+                f,
+                "[{:.2}s] persist {:?} {} force {:.2}N",
+                c.time, c.kind, c.pair_label(), c.force
+            ),
+            CollisionEvent::ContactEnd(c) => write!(
+                f,
+                "[{:.2}s] end     {:?} {} after {:.2}s",
+                c.time, c.kind, c.pair_label(), c.duration()
+            ),
+            CollisionEvent::Crash(c) => write!(
+                f,
+                "[{:.2}s] CRASH   {:?} {} at [{:.3}, {:.3}, {:.3}] normal [{:.2}, {:.2}, {:.2}] depth {:.4}m force {:.2}N speed {:.2}m/s",
+                c.time, c.kind, c.pair_label(), c.pos[0], c.pos[1], c.pos[2],
+                c.normal[0], c.normal[1], c.normal[2], -c.dist, c.force, c.speed
+            ),
+        }
+    }
+}
+
+/// Diffs the contacts of consecutive steps, call `update` after every step
+pub struct CollisionTracker {
+    pub thresholds: CrashThresholds,
+    active: BTreeMap<(usize, usize), TrackedContact>,
+    // geom velocities [ang, lin] & centers of the previous update, so that
+    // a new contact gets the speed from before the impact
+    prev_vel: Vec<[f64; 6]>,
+    prev_pos: Vec<[f64; 3]>,
+}
+
+impl CollisionTracker {
+    pub fn new(thresholds: CrashThresholds) -> Self {
+        CollisionTracker {
+            thresholds,
+            active: BTreeMap::new(),
+            prev_vel: Vec::new(),
+            prev_pos: Vec::new(),
+        }
+    }
+
+    pub fn update(&mut self, simulation: &Simulation) -> Vec<CollisionEvent> {
+        let time = unsafe { (*simulation.state.ptr()).time };
+        let (vel, pos) = geom_velocities(simulation);
+        if self.prev_vel.len() != vel.len() {
+            self.prev_vel = vel.clone();
+            self.prev_pos = pos.clone();
+        }
+
+        // merge the contact points of each geom pair
+        let mut current: BTreeMap<(usize, usize), Vec<ContactEvent>> = BTreeMap::new();
+        for event in contact::contacts(simulation) {
+            let key = (event.geom1.min(event.geom2), event.geom1.max(event.geom2));
+            current.entry(key).or_default().push(event);
+        }
+
+        let mut begin = Vec::new();
+        let mut persist = Vec::new();
+        let mut crash = Vec::new();
+        for (key, events) in current.iter() {
+            let previous = self.active.get(key);
+            // approach speed from before the impact for new contacts
+            let (v, c) = if previous.is_some() { (&vel, &pos) } else { (&self.prev_vel, &self.prev_pos) };
+            let mut tracked = merge(simulation, events, v, c, time);
+
+            match previous {
+                Some(prev) => {
+                    tracked.begin_time = prev.begin_time;
+                    tracked.crashed = prev.crashed;
+                    persist.push(CollisionEvent::ContactPersist(tracked.clone()));
+                }
+                None => begin.push(CollisionEvent::ContactBegin(tracked.clone())),
+            }
+
+            if !tracked.crashed
+                && tracked.kind != ContactKind::SelfCollision
+                && (tracked.force > self.thresholds.force || tracked.speed > self.thresholds.speed)
+            {
+                tracked.crashed = true;
+                crash.push(CollisionEvent::Crash(tracked.clone()));
+            }
+            self.active.insert(*key, tracked);
+        }
+
+        // pairs that stopped touching
+        let mut end = Vec::new();
+        let ended: Vec<(usize, usize)> = self.active.keys().filter(|k| !current.contains_key(k)).copied().collect();
+        for key in ended {
+            let mut tracked = self.active.remove(&key).unwrap();
+            tracked.time = time;
+            end.push(CollisionEvent::ContactEnd(tracked));
+        }
+
+        self.prev_vel = vel;
+        self.prev_pos = pos;
+
+        let mut events = begin;
+        events.extend(persist);
+        events.extend(end);
+        events.extend(crash);
+        events
+    }
+}
+
+// velocity [ang, lin] in the world frame & center of every geom
+fn geom_velocities(simulation: &Simulation) -> (Vec<[f64; 6]>, Vec<[f64; 3]>) {
+    let mut vel = Vec::new();
+    let mut pos = Vec::new();
+    unsafe {
+        let mj_model = &*simulation.model.ptr();
+        let mj_data = &*simulation.state.ptr();
+        for i in 0..mj_model.ngeom as usize {
+            let mut v = [0.0; 6];
+            // 5: mjOBJ_GEOM, 0: world frame
+            no_render::mj_objectVelocity(simulation.model.ptr(), simulation.state.ptr(), 5, i as i32, v.as_mut_ptr(), 0);
+            vel.push(v);
+            let p = mj_data.geom_xpos.add(3 * i);
+            pos.push([*p, *p.add(1), *p.add(2)]);
+        }
+    }
+    (vel, pos)
+}
+
+// velocity of a point rigidly attached to a geom
+fn point_velocity(vel: &[f64; 6], center: &[f64; 3], point: &[f64; 3]) -> [f64; 3] {
+    let r = [point[0] - center[0], point[1] - center[1], point[2] - center[2]];
+    [
+        vel[3] + vel[1] * r[2] - vel[2] * r[1],
+        vel[4] + vel[2] * r[0] - vel[0] * r[2],
+        vel[5] + vel[0] * r[1] - vel[1] * r[0],
+    ]
+}
+
+fn classify(simulation: &Simulation, event: &ContactEvent) -> ContactKind {
+    unsafe {
+        let mj_model = &*simulation.model.ptr();
+        let root1 = *mj_model.body_rootid.add(event.body1);
+        let root2 = *mj_model.body_rootid.add(event.body2);
+        // 0: mjGEOM_PLANE
+        let plane = |geom: usize, body: usize| body == 0 && *mj_model.geom_type.add(geom) == 0;
+        if plane(event.geom1, event.body1) || plane(event.geom2, event.body2) {
+            ContactKind::Ground
+        } else if root1 == root2 && root1 != 0 {
+            ContactKind::SelfCollision
+        } else {
+            ContactKind::Obstacle
+        }
+    }
+}
+
+fn merge(simulation: &Simulation, events: &[ContactEvent], vel: &[[f64; 6]], pos: &[[f64; 3]], time: f64) -> TrackedContact {
+    let first = &events[0];
+    let mut mean = [0.0; 3];
+    let mut force = 0.0;
+    let mut speed: f64 = 0.0;
+    let mut deepest = first;
+    for event in events {
+        for (m, p) in mean.iter_mut().zip(event.pos.iter()) {
+            *m += p / events.len() as f64;
+        }
+        force += event.force[0];
+        if event.dist < deepest.dist {
+            deepest = event;
+        }
+
+        // positive when geom2 moves towards geom1
+        let v1 = point_velocity(&vel[event.geom1], &pos[event.geom1], &event.pos);
+        let v2 = point_velocity(&vel[event.geom2], &pos[event.geom2], &event.pos);
+        let n = event.normal();
+        let approach = -((v2[0] - v1[0]) * n[0] + (v2[1] - v1[1]) * n[1] + (v2[2] - v1[2]) * n[2]);
+        speed = speed.max(approach);
+    }
+
+    TrackedContact {
+        geom1: first.geom1,
+        geom2: first.geom2,
+        geom1_name: first.geom1_name.clone(),
+        geom2_name: first.geom2_name.clone(),
+        kind: classify(simulation, first),
+        pos: mean,
+        normal: deepest.normal(),
+        dist: deepest.dist,
+        force,
+        speed,
+        begin_time: time,
+        time,
+        crashed: false,
+    }
+}
@@ -84,7 +84,49 @@ pub fn ui_init(glfw: &mut glfw::Glfw, simulation: &Simulation, cam_ids: &[i32])
     }
 }
 
+/// Contact point & force arrow drawn on top of the model
+#[derive(Debug, Clone)]
+pub struct ContactMarker {
+    pub pos: [f64; 3],
+    pub force: [f64; 3], // world frame (N)
+    pub rgba: [f32; 4],
+}
+
+// length of the force arrows (m/N)
+const FORCE_SCALE: f64 = 0.02;
+
 pub fn update_scene(simulation: &Simulation, ui_state: &mut UIState) {
+    update_scene_with_markers(simulation, ui_state, &[]);
+}
+
+// append markers to the geoms of the scene, skipped once the scene is full
+fn add_markers(scn: &mut render::mjvScene_, markers: &[ContactMarker]) {
+    for marker in markers {
+        if scn.ngeom + 2 > scn.maxgeom {
+            return;
+        }
+        unsafe {
+            // contact point
+            let size = [0.02, 0.0, 0.0];
+            no_render::mjv_initGeom(scn.geoms.add(scn.ngeom as usize), 2, size.as_ptr(), marker.pos.as_ptr(), ptr::null(), marker.rgba.as_ptr()); // 2: mjGEOM_SPHERE
+            scn.ngeom += 1;
+
+            // force arrow
+            let p = marker.pos;
+            let f = marker.force;
+            let geom = scn.geoms.add(scn.ngeom as usize);
+            no_render::mjv_initGeom(geom, 100, size.as_ptr(), p.as_ptr(), ptr::null(), marker.rgba.as_ptr()); // 100: mjGEOM_ARROW
+            no_render::mjv_makeConnector(
+                geom, 100, 0.01,
+                p[0], p[1], p[2],
+                p[0] + FORCE_SCALE * f[0], p[1] + FORCE_SCALE * f[1], p[2] + FORCE_SCALE * f[2],
+            );
+            scn.ngeom += 1;
+        }
+    }
+}
+
+pub fn update_scene_with_markers(simulation: &Simulation, ui_state: &mut UIState, markers: &[ContactMarker]) {
     ui_state.window.make_current();
     unsafe {
         // get window size
@@ -109,6 +151,7 @@ pub fn update_scene(simulation: &Simulation, ui_state: &mut UIState) {
                 0xFFFFFF,
                 &mut ui_state.scenes[i],
             );
+            add_markers(&mut ui_state.scenes[i], markers);
 
             // calc sub window's pos in the main window
             let row = i / cols;
@@ -0,0 +1,163 @@
+// editor.rs
+use mujoco_rust::model::ObjType;
+use mujoco_rust::Simulation;
+use std::collections::HashMap;
+
+/// Primitive shape of a spawned obstacle, the values are the `mjtGeom` types
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Shape {
+    Sphere = 2,
+    Cylinder = 5,
+    Box = 6,
+}
+
+/// A mocap body of the pool with its one geom
+#[derive(Debug, Clone)]
+struct Slot {
+    mocap: usize,
+    geom: usize,
+    in_use: bool,
+}
+
+/// Changes to the obstacles of a loaded scene while it runs, no XML is
+/// reloaded
+///
+/// Static geoms are edited in place in `mjModel` (`geom_pos`, `geom_size`,
+/// `geom_rgba`), the changes show from the next step on. MuJoCo can not add
+/// bodies at runtime, so new obstacles come from a pool of mocap bodies
+/// `mocap_obstacle1..n` in the XML (see `SceneConfig::mocap_pool`) that are
+/// parked and switched off until spawned.
+pub struct SceneEditor {
+    disabled: HashMap<usize, (i32, i32, f32)>, // geom -> contype, conaffinity & alpha before it was disabled
+    pool: Vec<Slot>,
+}
+
+impl SceneEditor {
+    /// Finds the mocap obstacle pool of the scene, it may be empty
+    pub fn new(simulation: &Simulation) -> Self {
+        let mut editor = SceneEditor { disabled: HashMap::new(), pool: Vec::new() };
+        let mj_model = unsafe { &*simulation.model.ptr() };
+        while let Some(body) = simulation.model.name_to_id(ObjType::BODY, &format!("mocap_obstacle{}", editor.pool.len() + 1)) {
+            let (mocap, geom) = unsafe { (*mj_model.body_mocapid.add(body as usize), *mj_model.body_geomadr.add(body as usize)) };
+            if mocap < 0 || geom < 0 {
+                eprintln!("mocap_obstacle{} needs mocap=\"true\" and a geom", editor.pool.len() + 1);
+                break;
+            }
+            // parked in the XML, enabling restores what a spawn sets
+            editor.disabled.insert(geom as usize, (1, 1, 1.0));
+            editor.pool.push(Slot { mocap: mocap as usize, geom: geom as usize, in_use: false });
+        }
+        editor
+    }
+
+    pub fn geom_id(simulation: &Simulation, name: &str) -> Option<usize> {
+        simulation.model.name_to_id(ObjType::GEOM, name).map(|id| id as usize)
+    }
+
+    /// Position in the frame of the geom's body, the world for the geoms
+    /// of the generated scenes
+    pub fn set_pos(simulation: &Simulation, geom: usize, pos: [f64; 3]) {
+        unsafe {
+            let mj_model = &*simulation.model.ptr();
+            std::slice::from_raw_parts_mut(mj_model.geom_pos.add(3 * geom), 3).copy_from_slice(&pos);
+        }
+    }
+
+    /// MJCF size of the geom, e.g. half lengths of a box or radius & half
+    /// height of a cylinder; the bounding sphere used by the collision
+    /// detection is updated with it
+    pub fn set_size(simulation: &Simulation, geom: usize, size: [f64; 3]) {
+        unsafe {
+            let mj_model = &*simulation.model.ptr();
+            std::slice::from_raw_parts_mut(mj_model.geom_size.add(3 * geom), 3).copy_from_slice(&size);
+            let [a, b, c] = size;
+            let rbound = match *mj_model.geom_type.add(geom) {
+                2 => a,                       // sphere
+                3 => a + b,                   // capsule
+                4 => a.max(b).max(c),         // ellipsoid
+                5 => (a * a + b * b).sqrt(),  // cylinder
+                6 => (a * a + b * b + c * c).sqrt(), // box
+                _ => *mj_model.geom_rbound.add(geom), // planes are infinite, meshes keep their own
+            };
+            *mj_model.geom_rbound.add(geom) = rbound;
+        }
+    }
+
+    /// Colour of the geom, the alpha is kept aside while it is disabled
+    pub fn set_rgba(&mut self, simulation: &Simulation, geom: usize, rgba: [f32; 4]) {
+        unsafe {
+            let mj_model = &*simulation.model.ptr();
+            let dst = std::slice::from_raw_parts_mut(mj_model.geom_rgba.add(4 * geom), 4);
+            dst[..3].copy_from_slice(&rgba[..3]);
+            match self.disabled.get_mut(&geom) {
+                Some(saved) => saved.2 = rgba[3],
+                None => dst[3] = rgba[3],
+            }
+        }
+    }
+
+    /// A disabled geom does not collide and is invisible, also to the
+    /// rangefinders as `mj_ray` skips geoms with alpha 0
+    pub fn set_enabled(&mut self, simulation: &Simulation, geom: usize, enabled: bool) {
+        unsafe {
+            let mj_model = &*simulation.model.ptr();
+            let contype = mj_model.geom_contype.add(geom);
+            let conaffinity = mj_model.geom_conaffinity.add(geom);
+            let alpha = mj_model.geom_rgba.add(4 * geom + 3);
+            if enabled {
+                if let Some((t, a, rgba)) = self.disabled.remove(&geom) {
+                    (*contype, *conaffinity, *alpha) = (t, a, rgba);
+                }
+            } else if !self.disabled.contains_key(&geom) {
+                self.disabled.insert(geom, (*contype, *conaffinity, *alpha));
+                (*contype, *conaffinity, *alpha) = (0, 0, 0.0);
+            }
+        }
+    }
+
+    pub fn is_enabled(&self, geom: usize) -> bool {
+        !self.disabled.contains_key(&geom)
+    }
+
+    /// Take a free mocap obstacle of the pool and put it at `pos`, `None`
+    /// when all are in use. The handle is the index of the slot.
+    pub fn spawn(&mut self, simulation: &Simulation, shape: Shape, pos: [f64; 3], size: [f64; 3], rgba: [f32; 4]) -> Option<usize> {
+        let handle = self.pool.iter().position(|slot| !slot.in_use)?;
+        let geom = self.pool[handle].geom;
+        unsafe {
+            // primitives only, their collision functions are picked by type every step
+            *(*simulation.model.ptr()).geom_type.add(geom) = shape as i32;
+        }
+        Self::set_size(simulation, geom, size);
+        self.set_rgba(simulation, geom, rgba);
+        self.set_enabled(simulation, geom, true);
+        self.move_spawned(simulation, handle, pos, 0.0);
+        self.pool[handle].in_use = true;
+        Some(handle)
+    }
+
+    /// Move a spawned obstacle, `yaw` in rad about z
+    pub fn move_spawned(&self, simulation: &Simulation, handle: usize, pos: [f64; 3], yaw: f64) {
+        let mocap = self.pool[handle].mocap;
+        unsafe {
+            let mj_data = &*simulation.state.ptr();
+            std::slice::from_raw_parts_mut(mj_data.mocap_pos.add(3 * mocap), 3).copy_from_slice(&pos);
+            let quat = [(yaw / 2.0).cos(), 0.0, 0.0, (yaw / 2.0).sin()];
+            std::slice::from_raw_parts_mut(mj_data.mocap_quat.add(4 * mocap), 4).copy_from_slice(&quat);
+        }
+    }
+
+    /// Switch a spawned obstacle off and give its slot back to the pool
+    pub fn despawn(&mut self, simulation: &Simulation, handle: usize) {
+        if let Some(slot) = self.pool.get(handle).filter(|slot| slot.in_use) {
+            let geom = slot.geom;
+            self.set_enabled(simulation, geom, false);
+            self.pool[handle].in_use = false;
+        }
+    }
+
+    /// Handles of the spawned obstacles
+    pub fn spawned(&self) -> Vec<usize> {
+        (0..self.pool.len()).filter(|&i| self.pool[i].in_use).collect()
+    }
+}
@@ -0,0 +1,357 @@
+// generator.rs
+use mujoco_compose::rng::Rng;
+use std::fmt::Write;
+use std::io;
+use std::path::{Path, PathBuf};
+
+/// Roads on a square grid with a building in the blocks between them, as in
+/// `street_scene.xml`
+#[derive(Debug, Clone)]
+pub struct Roads {
+    pub spacing: f64, // between road centre lines (m)
+    pub width: f64,   // m
+    pub buildings: f64, // chance that a block gets a building, 0..1
+    pub building_height: [f64; 2], // m
+    pub setback: f64, // from the road to the building (m)
+}
+
+/// What a scene is made of, the densities are per 100 m²
+#[derive(Debug, Clone)]
+pub struct SceneConfig {
+    pub half_size: f64, // the obstacles stay in [-half_size, half_size]² (m)
+    pub clearance: f64, // free radius around the start of the drone (m)
+    pub gap: f64,       // smallest free space between two obstacles (m)
+    pub cylinders: f64,
+    pub boxes: f64,
+    pub walls: f64,
+    pub cylinder_radius: [f64; 2], // m
+    pub box_size: [f64; 2],        // half width (m)
+    pub wall_length: [f64; 2],     // m
+    pub obstacle_height: [f64; 2], // m
+    pub roads: Option<Roads>,
+    pub mocap_pool: usize, // switched off mocap obstacles for `SceneEditor::spawn`
+}
+
+impl Default for SceneConfig {
+    /// An obstacle course like `simple_scene.xml` and `scene.xml`, over 20x20 m
+    fn default() -> Self {
+        SceneConfig {
+            half_size: 10.0,
+            clearance: 1.5,
+            gap: 0.8,
+            cylinders: 4.0,
+            boxes: 2.0,
+            walls: 0.5,
+            cylinder_radius: [0.2, 0.5],
+            box_size: [0.25, 0.6],
+            wall_length: [2.0, 6.0],
+            obstacle_height: [1.0, 3.5],
+            roads: None,
+            mocap_pool: 8,
+        }
+    }
+}
+
+impl SceneConfig {
+    /// A town like `street_scene.xml`, over 100x100 m with poles and boxes
+    /// beside the roads
+    pub fn street() -> Self {
+        SceneConfig {
+            half_size: 50.0,
+            clearance: 3.0,
+            gap: 1.5,
+            cylinders: 0.2,
+            boxes: 0.1,
+            walls: 0.0,
+            cylinder_radius: [0.1, 0.3],
+            box_size: [0.5, 1.5],
+            wall_length: [2.0, 6.0],
+            obstacle_height: [1.0, 6.0],
+            roads: Some(Roads { spacing: 25.0, width: 5.0, buildings: 0.8, building_height: [6.0, 30.0], setback: 2.0 }),
+            mocap_pool: 8,
+        }
+    }
+
+    /// All obstacle densities times `factor`, the buildings stay
+    pub fn scale_density(&mut self, factor: f64) {
+        self.cylinders *= factor;
+        self.boxes *= factor;
+        self.walls *= factor;
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Kind {
+    Road,
+    Building,
+    Wall,
+    Cylinder,
+    Box,
+}
+
+impl Kind {
+    pub const ALL: [Kind; 5] = [Kind::Road, Kind::Building, Kind::Wall, Kind::Cylinder, Kind::Box];
+
+    /// Prefix of the geom names, the obstacles of the hand-written scenes
+    /// are named the same way
+    pub fn name(&self) -> &'static str {
+        match self {
+            Kind::Road => "road",
+            Kind::Building => "building",
+            Kind::Wall => "wall",
+            Kind::Cylinder => "cylinder_obstacle",
+            Kind::Box => "box_obstacle",
+        }
+    }
+}
+
+/// One geom of a generated scene
+#[derive(Debug, Clone)]
+pub struct Obstacle {
+    pub name: String,
+    pub kind: Kind,
+    pub pos: [f64; 3],
+    pub size: [f64; 3], // MJCF size: half lengths, or radius & half height
+    pub yaw: f64,       // deg
+    pub rgba: [f64; 4],
+}
+
+impl Obstacle {
+    /// Axis aligned bounds on the ground, [x_min, y_min, x_max, y_max]
+    fn footprint(&self) -> [f64; 4] {
+        let (hx, hy) = match self.kind {
+            Kind::Cylinder => (self.size[0], self.size[0]),
+            _ => {
+                let (s, c) = self.yaw.to_radians().sin_cos();
+                (self.size[0] * c.abs() + self.size[1] * s.abs(), self.size[0] * s.abs() + self.size[1] * c.abs())
+            }
+        };
+        [self.pos[0] - hx, self.pos[1] - hy, self.pos[0] + hx, self.pos[1] + hy]
+    }
+
+    fn to_mjcf(&self) -> String {
+        let geom_type = if self.kind == Kind::Cylinder { "cylinder" } else { "box" };
+        let size = if self.kind == Kind::Cylinder {
+            format!("{:.3} {:.3}", self.size[0], self.size[1])
+        } else {
+            format!("{:.3} {:.3} {:.3}", self.size[0], self.size[1], self.size[2])
+        };
+        let euler = if self.yaw != 0.0 { format!(" euler=\"0 0 {:.1}\"", self.yaw) } else { String::new() };
+        let [r, g, b, a] = self.rgba;
+        format!(
+            "<geom name=\"{}\" type=\"{}\" size=\"{}\" pos=\"{:.3} {:.3} {:.3}\"{} rgba=\"{} {} {} {}\"/>",
+            self.name, geom_type, size, self.pos[0], self.pos[1], self.pos[2], euler, r, g, b, a
+        )
+    }
+}
+
+/// A generated layout, the same config and seed always give the same scene
+#[derive(Debug, Clone)]
+pub struct Scene {
+    pub seed: u64,
+    pub obstacles: Vec<Obstacle>,
+    pub mocap_pool: usize,
+}
+
+impl Scene {
+    /// Roads first, then the buildings in the blocks, then the walls,
+    /// cylinders and boxes wherever they do not overlap what is already there
+    pub fn generate(config: &SceneConfig, seed: u64) -> Self {
+        let mut rng = Rng::new(seed);
+        let mut scene = Scene { seed, obstacles: Vec::new(), mocap_pool: config.mocap_pool };
+        // roads are not obstacles for the drone but the others keep off them
+        let mut occupied: Vec<[f64; 4]> = Vec::new();
+        let half = config.half_size;
+
+        if let Some(roads) = &config.roads {
+            let lines: Vec<f64> = (-(half / roads.spacing).floor() as i64..=(half / roads.spacing).floor() as i64)
+                .map(|k| k as f64 * roads.spacing)
+                .collect();
+            for (axis, label) in ["h", "v"].iter().enumerate() {
+                for (i, &line) in lines.iter().enumerate() {
+                    let mut pos = [0.0, 0.0, 0.01];
+                    let mut size = [half, half, 0.01];
+                    pos[1 - axis] = line;
+                    size[1 - axis] = roads.width / 2.0;
+                    let road = Obstacle {
+                        name: format!("road_{}{}", label, i + 1),
+                        kind: Kind::Road,
+                        pos,
+                        size,
+                        yaw: 0.0,
+                        rgba: [0.3, 0.3, 0.3, 1.0],
+                    };
+                    occupied.push(road.footprint());
+                    scene.obstacles.push(road);
+                }
+            }
+
+            // blocks between the roads and the edge of the area
+            let mut bounds = vec![-half];
+            bounds.extend(lines.iter().copied());
+            bounds.push(half);
+            bounds.dedup();
+            let edge = |i: usize, side: f64| {
+                let b = bounds[i];
+                if lines.contains(&b) { b + side * (roads.width / 2.0 + roads.setback) } else { b }
+            };
+            for ix in 0..bounds.len() - 1 {
+                for iy in 0..bounds.len() - 1 {
+                    let (x0, x1) = (edge(ix, 1.0), edge(ix + 1, -1.0));
+                    let (y0, y1) = (edge(iy, 1.0), edge(iy + 1, -1.0));
+                    if x1 - x0 < 2.0 || y1 - y0 < 2.0 || rng.uniform() >= roads.buildings {
+                        continue;
+                    }
+                    // somewhere between half and all of the block
+                    let hx = (x1 - x0) / 2.0 * rng.range([0.5, 1.0]);
+                    let hy = (y1 - y0) / 2.0 * rng.range([0.5, 1.0]);
+                    let x = rng.range([x0 + hx, x1 - hx]);
+                    let y = rng.range([y0 + hy, y1 - hy]);
+                    let hz = rng.range(roads.building_height) / 2.0;
+                    let building = Obstacle {
+                        name: String::new(),
+                        kind: Kind::Building,
+                        pos: [x, y, hz],
+                        size: [hx, hy, hz],
+                        yaw: 0.0,
+                        rgba: [0.5, 0.5, 0.5, 1.0],
+                    };
+                    if clear_of_start(&building.footprint(), config.clearance) {
+                        occupied.push(building.footprint());
+                        scene.push(building);
+                    }
+                }
+            }
+        }
+
+        // the area in 100 m²
+        let area = (2.0 * half) * (2.0 * half) / 100.0;
+        for kind in [Kind::Wall, Kind::Cylinder, Kind::Box] {
+            let density = match kind {
+                Kind::Wall => config.walls,
+                Kind::Cylinder => config.cylinders,
+                _ => config.boxes,
+            };
+            for _ in 0..rng.poisson(density * area) {
+                // a few tries to find a free spot, then give up on this one
+                for _ in 0..20 {
+                    let obstacle = random_obstacle(config, kind, &mut rng);
+                    let footprint = obstacle.footprint();
+                    let free = occupied.iter().all(|other| !overlaps(&footprint, other, config.gap));
+                    if free && clear_of_start(&footprint, config.clearance) {
+                        occupied.push(footprint);
+                        scene.push(obstacle);
+                        break;
+                    }
+                }
+            }
+        }
+        scene
+    }
+
+    /// Add an obstacle named after its kind, e.g. `cylinder_obstacle3`
+    fn push(&mut self, mut obstacle: Obstacle) {
+        let n = self.count(obstacle.kind) + 1;
+        obstacle.name = format!("{}{}", obstacle.kind.name(), n);
+        self.obstacles.push(obstacle);
+    }
+
+    pub fn count(&self, kind: Kind) -> usize {
+        self.obstacles.iter().filter(|o| o.kind == kind).count()
+    }
+
+    /// The scene as MJCF, with the same visual settings and floor as the
+    /// scenes in `x2/` and the drone from `x2.xml`
+    pub fn to_mjcf(&self) -> String {
+        let mut xml = String::new();
+        let _ = writeln!(xml, "<mujoco model=\"Skydio X2 generated scene {}\">", self.seed);
+        xml.push_str(
+            r#"  <include file="x2.xml"/>
+  <statistic center="0 0 0.1" extent="0.6" meansize=".05"/>
+
+  <visual>
+    <headlight diffuse="0.6 0.6 0.6" ambient="0.3 0.3 0.3" specular="0 0 0"/>
+    <rgba haze="0.15 0.25 0.35 1"/>
+    <global azimuth="-20" elevation="-20" ellipsoidinertia="true"/>
+  </visual>
+
+  <asset>
+    <texture type="skybox" builtin="gradient" rgb1="0.3 0.5 0.7" rgb2="0 0 0" width="512" height="3072"/>
+    <texture type="2d" name="groundplane" builtin="checker" mark="edge" rgb1="0.2 0.3 0.4" rgb2="0.1 0.2 0.3"
+      markrgb="0.8 0.8 0.8" width="300" height="300"/>
+    <material name="groundplane" texture="groundplane" texuniform="true" texrepeat="5 5" reflectance="0.2"/>
+  </asset>
+
+  <worldbody>
+    <light pos="0 0 1.5" dir="0 0 -1" directional="true"/>
+    <geom name="floor" size="0 0 0.05" type="plane" material="groundplane"/>
+"#,
+        );
+        for kind in Kind::ALL {
+            if self.count(kind) == 0 {
+                continue;
+            }
+            let _ = writeln!(xml, "\n    <!-- {}s -->", kind.name().replace('_', " "));
+            for obstacle in self.obstacles.iter().filter(|o| o.kind == kind) {
+                let _ = writeln!(xml, "    {}", obstacle.to_mjcf());
+            }
+        }
+        if self.mocap_pool > 0 {
+            xml.push_str("\n    <!-- mocap obstacles, parked below the floor until spawned -->\n");
+        }
+        for i in 1..=self.mocap_pool {
+            let _ = writeln!(
+                xml,
+                "    <body name=\"mocap_obstacle{0}\" mocap=\"true\" pos=\"0 0 -10\">\n      <geom name=\"mocap_obstacle{0}\" type=\"box\" size=\"0.5 0.5 0.5\" contype=\"0\" conaffinity=\"0\" rgba=\"0.8 0.5 0.2 0\"/>\n    </body>",
+                i
+            );
+        }
+        xml.push_str("\n  </worldbody>\n</mujoco>\n");
+        xml
+    }
+
+    /// Write `gen_scene_<seed>.xml` into `dir`, which has to be the folder of
+    /// `x2.xml` for the include and the meshes of the drone to be found
+    pub fn write(&self, dir: &Path) -> io::Result<PathBuf> {
+        let path = dir.join(format!("gen_scene_{}.xml", self.seed));
+        std::fs::write(&path, self.to_mjcf())?;
+        Ok(path)
+    }
+}
+
+fn random_obstacle(config: &SceneConfig, kind: Kind, rng: &mut Rng) -> Obstacle {
+    let half = config.half_size;
+    let hz = rng.range(config.obstacle_height) / 2.0;
+    let (size, yaw, rgba) = match kind {
+        Kind::Cylinder => ([rng.range(config.cylinder_radius), hz, 0.0], 0.0, [0.8, 0.2, 0.2, 1.0]),
+        Kind::Box => {
+            let s = rng.range(config.box_size);
+            ([s, s, hz], rng.range([0.0, 90.0]), [0.2, 0.8, 0.2, 1.0])
+        }
+        _ => {
+            // along x or y, like the walls of scene.xml
+            let length = rng.range(config.wall_length) / 2.0;
+            let yaw = if rng.uniform() < 0.5 { 0.0 } else { 90.0 };
+            ([length, 0.01, hz], yaw, [0.7, 0.7, 0.7, 1.0])
+        }
+    };
+    Obstacle {
+        name: String::new(),
+        kind,
+        pos: [rng.range([-half, half]), rng.range([-half, half]), hz],
+        size,
+        yaw,
+        rgba,
+    }
+}
+
+fn overlaps(a: &[f64; 4], b: &[f64; 4], gap: f64) -> bool {
+    a[0] < b[2] + gap && b[0] < a[2] + gap && a[1] < b[3] + gap && b[1] < a[3] + gap
+}
+
+/// Nothing within `clearance` of the origin, where the drone starts
+fn clear_of_start(footprint: &[f64; 4], clearance: f64) -> bool {
+    let dx = footprint[0].max(0.0).max(-footprint[2]);
+    let dy = footprint[1].max(0.0).max(-footprint[3]);
+    dx * dx + dy * dy >= clearance * clearance
+}
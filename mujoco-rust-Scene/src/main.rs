@@ -0,0 +1,185 @@
+// main.rs
+mod editor;
+mod generator;
+mod ui;
+
+use mujoco_compose::compose::{Composer, Drone, Mount};
+use editor::{SceneEditor, Shape};
+use generator::{Kind, Scene, SceneConfig};
+use std::path::PathBuf;
+
+// usage: mujoco-rust-Scene [--seed <n>] [--count <n>] [--out <dir>] [--street] [--density <factor>] [--view]
+//        mujoco-rust-Scene --compose <world.xml> [--drone <file> [--prefix <p>] [--at <x,y,z>] [--mount <m>]]... [--view]
+// where <m> is free, fixed, hinge-x|y|z or slide-x|y|z
+
+fn main() -> Result<(), Box<dyn std::error::Error>> {
+    let mut seed: u64 = 0;
+    let mut count: u64 = 1;
+    let mut out = PathBuf::from("../x2");
+    let mut config = SceneConfig::default();
+    let mut density = 1.0;
+    let mut view = false;
+    let mut composer: Option<Composer> = None;
+
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--seed" => seed = args.next().and_then(|s| s.parse().ok()).unwrap_or(0),
+            "--count" => count = args.next().and_then(|s| s.parse().ok()).unwrap_or(1),
+            "--out" => out = args.next().map(PathBuf::from).unwrap_or(out),
+            "--street" => config = SceneConfig::street(),
+            "--density" => density = args.next().and_then(|s| s.parse().ok()).unwrap_or(1.0),
+            "--view" => view = true,
+            "--compose" => composer = args.next().map(Composer::new),
+            "--drone" => match (composer.as_mut(), args.next()) {
+                (Some(composer), Some(file)) => composer.drones.push(Drone::new(file)),
+                _ => eprintln!("--drone <file> goes after --compose <world>"),
+            },
+            // the options below apply to the last --drone
+            "--prefix" => match (last_drone(&mut composer), args.next()) {
+                (Some(drone), Some(prefix)) => drone.prefix = prefix,
+                _ => eprintln!("--prefix <p> goes after --drone <file>"),
+            },
+            "--at" => match (last_drone(&mut composer), args.next().and_then(|s| parse_list(&s))) {
+                (Some(drone), Some(v)) if v.len() == 3 => drone.pos = Some([v[0], v[1], v[2]]),
+                _ => eprintln!("--at x,y,z goes after --drone <file>"),
+            },
+            "--mount" => match (last_drone(&mut composer), args.next().and_then(|s| parse_mount(&s))) {
+                (Some(drone), Some(mount)) => drone.mount = mount,
+                _ => eprintln!("--mount free|fixed|hinge-x|...|slide-z goes after --drone <file>"),
+            },
+            _ => eprintln!("unknown argument: {}", arg),
+        }
+    }
+    config.scale_density(density);
+
+    // a world with drones attached instead of a generated scene
+    if let Some(mut composer) = composer {
+        if composer.drones.is_empty() {
+            composer.drones.push(Drone::new("../x2/x2.xml"));
+        }
+        let path = composer.write_temp()?;
+        println!("{} + {} drone(s) composed into {}", composer.world.display(), composer.drones.len(), path.display());
+        if view {
+            let simulation = mujoco_rust::Simulation::new(composer.load()?);
+            view_scene(&simulation, None);
+        }
+        return Ok(());
+    }
+
+    println!("Generating {} scene(s) from seed {}...", count, seed);
+    println!("--------------------------------------------------");
+
+    let mut first: Option<(Scene, PathBuf)> = None;
+    for s in seed..seed + count {
+        let scene = Scene::generate(&config, s);
+        let path = scene.write(&out)?;
+        let counts: Vec<String> = Kind::ALL
+            .iter()
+            .filter(|&&kind| scene.count(kind) > 0)
+            .map(|&kind| format!("{} {}", scene.count(kind), kind.name()))
+            .collect();
+        println!("{}: {}", path.display(), counts.join(", "));
+        first.get_or_insert((scene, path));
+    }
+
+    println!("--------------------------------------------------");
+
+    if let (true, Some((scene, path))) = (view, first) {
+        let model = mujoco_rust::Model::from_xml(path.to_string_lossy().to_string()).unwrap();
+        let simulation = mujoco_rust::Simulation::new(model);
+        view_scene(&simulation, Some(scene));
+    }
+    Ok(())
+}
+
+fn last_drone(composer: &mut Option<Composer>) -> Option<&mut Drone> {
+    composer.as_mut().and_then(|c| c.drones.last_mut())
+}
+
+fn parse_list(s: &str) -> Option<Vec<f64>> {
+    s.split(',').map(|v| v.trim().parse().ok()).collect()
+}
+
+fn parse_mount(s: &str) -> Option<Mount> {
+    let axis = |a: &str| ["x", "y", "z"].iter().position(|&n| n == a);
+    match s.split_once('-') {
+        None if s == "free" => Some(Mount::Free),
+        None if s == "fixed" => Some(Mount::Fixed),
+        Some(("hinge", a)) => axis(a).map(Mount::hinge),
+        Some(("slide", a)) => axis(a).map(Mount::slide),
+        _ => None,
+    }
+}
+
+/// Show a scene with the drones at hover, the obstacles can be changed
+/// while it runs
+fn view_scene(simulation: &mujoco_rust::Simulation, mut scene: Option<Scene>) {
+    println!("{} geoms", simulation.model.geoms().len());
+
+    let actuator_num = unsafe { (*simulation.model.ptr()).nu };
+    let mut ctrl: Vec<f64> = vec![0.0; actuator_num as usize];
+
+    let mut glfw = glfw::init(glfw::FAIL_ON_ERRORS).unwrap();
+    let mut ui_state = ui::ui_init(&mut glfw, simulation);
+
+    let mut editor = SceneEditor::new(simulation);
+    let mut rng = mujoco_compose::rng::Rng::new(scene.as_ref().map_or(0, |s| s.seed));
+    let walls: Vec<usize> = (1..).map_while(|i| SceneEditor::geom_id(simulation, &format!("wall{}", i))).collect();
+
+    while !ui_state.window.should_close() {
+        ui::update_scene(simulation, &mut ui_state);
+
+        // ctrl of the hover keyframe of x2.xml, for every drone
+        ctrl.fill(3.2495625);
+        simulation.control(&ctrl);
+        simulation.step();
+
+        glfw.poll_events();
+        for (_, event) in glfw::flush_messages(&ui_state.events) {
+            let glfw::WindowEvent::Key(key, _, glfw::Action::Press, _) = event else {
+                continue;
+            };
+            match key {
+                // spawn a box or cylinder from the mocap pool near the drone
+                glfw::Key::S => {
+                    let shape = [Shape::Box, Shape::Cylinder, Shape::Sphere][(rng.uniform() * 3.0) as usize];
+                    let pos = [rng.range([-4.0, 4.0]), rng.range([-4.0, 4.0]), 1.0];
+                    match editor.spawn(simulation, shape, pos, [0.4, 0.4, 1.0], [0.8, 0.5, 0.2, 1.0]) {
+                        Some(handle) => println!("spawned mocap_obstacle{} at {:.1?}", handle + 1, pos),
+                        None => println!("mocap pool is empty"),
+                    }
+                }
+                // remove the last one spawned
+                glfw::Key::X => {
+                    if let Some(&handle) = editor.spawned().last() {
+                        editor.despawn(simulation, handle);
+                    }
+                }
+                // switch the walls on and off
+                glfw::Key::D => {
+                    for &wall in walls.iter() {
+                        let enabled = editor.is_enabled(wall);
+                        editor.set_enabled(simulation, wall, !enabled);
+                    }
+                }
+                // grow the cylinders by a quarter, still standing on the floor
+                glfw::Key::G => {
+                    let obstacles = scene.as_mut().map_or(&mut [][..], |s| &mut s.obstacles[..]);
+                    for obstacle in obstacles.iter_mut().filter(|o| o.kind == Kind::Cylinder) {
+                        let Some(geom) = SceneEditor::geom_id(simulation, &obstacle.name) else {
+                            continue;
+                        };
+                        obstacle.size = obstacle.size.map(|s| s * 1.25);
+                        obstacle.pos[2] = obstacle.size[1];
+                        SceneEditor::set_size(simulation, geom, obstacle.size);
+                        SceneEditor::set_pos(simulation, geom, obstacle.pos);
+                    }
+                }
+                _ => {}
+            }
+        }
+    }
+
+    ui::free_resource(&mut ui_state);
+}
@@ -1,4 +1,6 @@
 // main.rs
+mod plot;
+
 use mujoco_rust::model::ObjType;
 
 use std::ffi::CStr;
@@ -71,21 +73,29 @@ fn main() {
     println!("{:?}", model_names);
 
 
-    // sim running for a while
-    for _ in 0..500 {
-        // get data from id
-        for id in rf_ids.iter() {
-            let data = simulation.sensordata()[*id as usize + 1];
-            print!("rf_{}: {:?} ", id, data);
-        }
+    // time window of the plot in seconds, first command line argument
+    let time_window: f64 = std::env::args().nth(1).and_then(|s| s.parse().ok()).unwrap_or(10.0);
+
+    // init glfw & plot window, one panel per group of channels
+    let mut glfw = glfw::init(glfw::FAIL_ON_ERRORS).unwrap();
+    let mut sensor_plot = plot::plot_init(&mut glfw, &simulation, time_window);
+
+    let gyro_panel = sensor_plot.add_panel("gyro (rad/s)");
+    sensor_plot.add_sensor(gyro_panel, &simulation, "body_gyro");
+    let acc_panel = sensor_plot.add_panel("accelerometer (m/s^2)");
+    sensor_plot.add_sensor(acc_panel, &simulation, "body_linacc");
+    let att_panel = sensor_plot.add_panel("attitude quaternion");
+    sensor_plot.add_sensor(att_panel, &simulation, "body_quat");
+    let ctrl_panel = sensor_plot.add_panel("motor ctrl");
+    for name in ["thrust1", "thrust2", "thrust3", "thrust4"] {
+        sensor_plot.add_ctrl(ctrl_panel, &simulation, name);
+    }
+    let alt_panel = sensor_plot.add_panel("altitude & front range (m)");
+    sensor_plot.add_height(alt_panel, &simulation, "x2");
+    sensor_plot.add_sensor(alt_panel, &simulation, "body_rf_0");
 
-        // update sensor data
-        let gyro_data = &simulation.sensordata()[gyro_start..gyro_start + gyro_dim];
-        let acc_data = &simulation.sensordata()[acc_start..acc_start + acc_dim];
-        let att_data = &simulation.sensordata()[att_start..att_start + att_dim];
-        
-        println!("\ngyro_data:{:?}\n acc_data:{:?}\n att_data:{:?}", gyro_data, acc_data, att_data);
-     
+    // sim running until the plot window closes
+    while !sensor_plot.window.should_close() {
         // ctrl array fixed settings
         ctrl[..4].fill(4.5);
         simulation.control(&ctrl);
@@ -93,7 +103,27 @@ fn main() {
         // sim forward a step
         simulation.step();
 
+        // sample & draw the selected channels
+        sensor_plot.record(&simulation);
+        sensor_plot.render();
+
+        // Get events in real time
+        glfw.poll_events();
+        glfw::flush_messages(&sensor_plot.events);
+    }
+
+    // print the last sensor readings
+    for id in rf_ids.iter() {
+        let data = simulation.sensordata()[*id as usize + 1];
+        print!("rf_{}: {:?} ", id, data);
     }
+    let gyro_data = &simulation.sensordata()[gyro_start..gyro_start + gyro_dim];
+    let acc_data = &simulation.sensordata()[acc_start..acc_start + acc_dim];
+    let att_data = &simulation.sensordata()[att_start..att_start + att_dim];
+    println!("\ngyro_data:{:?}\n acc_data:{:?}\n att_data:{:?}", gyro_data, acc_data, att_data);
+
+    // free glfw resources
+    plot::free_resource(&mut sensor_plot);
 
     println!("--------------------------------------------------");
     println!("Sim Done.");
@@ -0,0 +1,228 @@
+// plot.rs
+use mujoco_rs_sys::render;
+use mujoco_rust::model::ObjType;
+use mujoco_rust::Simulation;
+use glfw;
+use glfw::Context;
+use gl;
+use std::collections::VecDeque;
+use std::os::raw::c_char;
+use std::sync::mpsc;
+
+// limits of mjvFigure (mjMAXLINE, mjMAXLINEPNT)
+const MAX_LINES: usize = 100;
+const MAX_LINE_POINTS: usize = 1000;
+
+const LINE_COLORS: [[f32; 3]; 6] = [
+    [1.0, 0.3, 0.3],
+    [0.3, 1.0, 0.3],
+    [0.3, 0.5, 1.0],
+    [1.0, 1.0, 0.3],
+    [1.0, 0.3, 1.0],
+    [0.3, 1.0, 1.0],
+];
+
+/// Where a plotted value is read from each step
+pub enum Source {
+    Sensor(usize), // index in sensordata
+    Ctrl(usize),   // actuator index
+    Height(usize), // z of the body position in the world frame
+}
+
+struct Channel {
+    source: Source,
+    history: VecDeque<(f64, f64)>, // (time, value)
+}
+
+struct Panel {
+    title: String,
+    channels: Vec<Channel>,
+    fig: Box<render::mjvFigure_>,
+}
+
+pub struct SensorPlot {
+    pub time_window: f64, // seconds shown on the x axis
+    panels: Vec<Panel>,
+    con: render::mjrContext_,
+    pub window: glfw::Window,
+    pub events: mpsc::Receiver<(f64, glfw::WindowEvent)>,
+}
+
+pub fn plot_init(glfw: &mut glfw::Glfw, simulation: &Simulation, time_window: f64) -> SensorPlot {
+    // create window
+    let (mut window, events) = glfw
+        .create_window(800, 900, "MuJoCo Sensor Plot", glfw::WindowMode::Windowed)
+        .expect("Unable to create GLFW window.");
+
+    // associate GLFW window with an OpenGL state
+    window.make_current();
+    window.set_key_polling(true);
+
+    gl::load_with(|symbol| window.get_proc_address(symbol) as *const _);
+
+    // figures only need the fonts of the context
+    let mut con = render::mjrContext_::default();
+    unsafe {
+        render::mjr_defaultContext(&mut con);
+        render::mjr_makeContext(simulation.model.ptr(), &mut con, 150);
+    }
+
+    SensorPlot {
+        time_window,
+        panels: Vec::new(),
+        con,
+        window,
+        events,
+    }
+}
+
+impl SensorPlot {
+    /// Add an empty figure below the existing ones, returns its index
+    pub fn add_panel(&mut self, title: &str) -> usize {
+        let mut fig: Box<render::mjvFigure_> = Box::default();
+        unsafe {
+            render::mjv_defaultFigure(fig.as_mut());
+        }
+        fig.flg_legend = 1;
+        fig.flg_extend = 1;
+        fig.gridsize = [5, 5];
+        fig.range[1] = [0.0, 1.0];
+        copy_str(&mut fig.title, title);
+        copy_str(&mut fig.xlabel, "time (s)");
+
+        self.panels.push(Panel {
+            title: title.to_string(),
+            channels: Vec::new(),
+            fig,
+        });
+        self.panels.len() - 1
+    }
+
+    fn add_channel(&mut self, panel: usize, label: String, source: Source) {
+        let panel = &mut self.panels[panel];
+        if panel.channels.len() >= MAX_LINES {
+            eprintln!("panel '{}' is full, channel '{}' ignored", panel.title, label);
+            return;
+        }
+        let line = panel.channels.len();
+        panel.fig.linergb[line] = LINE_COLORS[line % LINE_COLORS.len()];
+        copy_str(&mut panel.fig.linename[line], &label);
+        panel.channels.push(Channel {
+            source,
+            history: VecDeque::new(),
+        });
+    }
+
+    /// Plot every component of a sensor, e.g. x/y/z of `body_gyro`
+    pub fn add_sensor(&mut self, panel: usize, simulation: &Simulation, sensor_name: &str) {
+        let id = simulation.model.name_to_id(ObjType::SENSOR, sensor_name).unwrap() as usize;
+        let (adr, dim) = unsafe {
+            let mj_model = &*simulation.model.ptr();
+            (*mj_model.sensor_adr.add(id) as usize, *mj_model.sensor_dim.add(id) as usize)
+        };
+        for i in 0..dim {
+            let label = if dim == 1 { sensor_name.to_string() } else { format!("{}[{}]", sensor_name, i) };
+            self.add_channel(panel, label, Source::Sensor(adr + i));
+        }
+    }
+
+    /// Plot the control value of an actuator, e.g. `thrust1`
+    pub fn add_ctrl(&mut self, panel: usize, simulation: &Simulation, actuator_name: &str) {
+        let id = simulation.model.name_to_id(ObjType::ACTUATOR, actuator_name).unwrap() as usize;
+        self.add_channel(panel, actuator_name.to_string(), Source::Ctrl(id));
+    }
+
+    /// Plot the height of a body above z = 0, e.g. `x2`
+    pub fn add_height(&mut self, panel: usize, simulation: &Simulation, body_name: &str) {
+        let id = simulation.model.name_to_id(ObjType::BODY, body_name).unwrap() as usize;
+        self.add_channel(panel, format!("{} z", body_name), Source::Height(id));
+    }
+
+    /// Sample all channels at the current simulation time
+    pub fn record(&mut self, simulation: &Simulation) {
+        let mj_data = unsafe { &*simulation.state.ptr() };
+        let time = mj_data.time;
+        let sensordata = simulation.sensordata();
+
+        for panel in self.panels.iter_mut() {
+            for channel in panel.channels.iter_mut() {
+                let value = match channel.source {
+                    Source::Sensor(i) => sensordata[i],
+                    Source::Ctrl(i) => unsafe { *mj_data.ctrl.add(i) },
+                    Source::Height(i) => unsafe { *mj_data.xpos.add(3 * i + 2) },
+                };
+                // the simulation was reset, start over
+                if channel.history.back().is_some_and(|&(t, _)| t > time) {
+                    channel.history.clear();
+                }
+                channel.history.push_back((time, value));
+                while channel.history.front().is_some_and(|&(t, _)| t < time - self.time_window) {
+                    channel.history.pop_front();
+                }
+            }
+        }
+    }
+
+    /// Draw all panels stacked vertically
+    pub fn render(&mut self) {
+        self.window.make_current();
+        let (width, height) = self.window.get_framebuffer_size();
+        let num_panels = self.panels.len().max(1) as i32;
+        let panel_height = height / num_panels;
+
+        unsafe {
+            gl::Clear(gl::COLOR_BUFFER_BIT | gl::DEPTH_BUFFER_BIT);
+        }
+
+        for (i, panel) in self.panels.iter_mut().enumerate() {
+            let mut latest = 0.0;
+            for (line, channel) in panel.channels.iter().enumerate() {
+                // keep at most MAX_LINE_POINTS by skipping samples evenly
+                let n = channel.history.len();
+                let stride = n.div_ceil(MAX_LINE_POINTS).max(1);
+                let mut count = 0;
+                for &(t, v) in channel.history.iter().step_by(stride) {
+                    panel.fig.linedata[line][2 * count] = t as f32;
+                    panel.fig.linedata[line][2 * count + 1] = v as f32;
+                    count += 1;
+                }
+                panel.fig.linepnt[line] = count as i32;
+                if let Some(&(t, _)) = channel.history.back() {
+                    latest = t;
+                }
+            }
+
+            // scroll the x axis with the simulation time
+            let start = (latest - self.time_window).max(0.0);
+            panel.fig.range[0] = [start as f32, (start + self.time_window) as f32];
+
+            let viewport = render::mjrRect_ {
+                left: 0,
+                bottom: height - (i as i32 + 1) * panel_height,
+                width,
+                height: panel_height,
+            };
+            unsafe {
+                render::mjr_figure(viewport, panel.fig.as_mut(), &self.con);
+            }
+        }
+
+        // swap buffer to display the figures
+        self.window.swap_buffers();
+    }
+}
+
+// copy a rust string into a fixed size, nul terminated C buffer
+fn copy_str(dst: &mut [c_char], src: &str) {
+    let n = src.len().min(dst.len() - 1);
+    for (d, s) in dst.iter_mut().zip(src.bytes().take(n)) {
+        *d = s as c_char;
+    }
+    dst[n] = 0;
+}
+
+pub fn free_resource(plot: &mut SensorPlot) {
+    unsafe {
+        render::mjr_freeContext(&mut plot.con);
+    }
+}